@@ -0,0 +1,58 @@
+use serde_json::Value;
+
+use crate::embed::Embed;
+use crate::DeltaOp;
+
+// external resources referenced by embeds and links
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AssetKind {
+    Image,
+    SavvyImage,
+    SavvyAttach,
+    Video,
+    /// a `link` attribute, never passed to `RenderOptions::url_rewriter`
+    Link,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Asset {
+    pub op_index: usize,
+    pub kind: AssetKind,
+    pub url: String,
+    pub alt: Option<String>,
+}
+
+// every external resource of a delta, matched the same way `parser` matches embeds
+pub fn assets(delta_ops: &[DeltaOp]) -> Vec<Asset> {
+    let mut assets = Vec::new();
+    for (op_index, op) in delta_ops.iter().enumerate() {
+        let attr = match &op.attributes {
+            Some(Value::Object(attr)) => Some(attr),
+            _ => None,
+        };
+        let alt = attr
+            .and_then(|attr| attr.get("alt"))
+            .and_then(|v| v.as_str())
+            .map(String::from);
+
+        if let Value::Object(obj_insert) = &op.insert {
+            if let Some((kind, url)) = Embed::from_insert(obj_insert).and_then(|e| e.asset()) {
+                assets.push(Asset {
+                    op_index,
+                    kind,
+                    url: String::from(url),
+                    alt: alt.clone(),
+                });
+            }
+        }
+        if let Some(Value::String(link)) = attr.and_then(|attr| attr.get("link")) {
+            assets.push(Asset {
+                op_index,
+                kind: AssetKind::Link,
+                url: link.clone(),
+                alt,
+            });
+        }
+    }
+    assets
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttachmentKind {
+    Image,
+    Video,
+    Audio,
+    Download,
+}
+
+// how `savvy_attach` embeds are rendered, looked up by mime type first, then by extension
+pub struct AttachmentTable {
+    extensions: HashMap<String, AttachmentKind>,
+    mime_types: HashMap<String, AttachmentKind>,
+}
+
+impl Default for AttachmentTable {
+    fn default() -> Self {
+        let mut table = AttachmentTable::new()
+            .mime_type("image", AttachmentKind::Image)
+            .mime_type("video", AttachmentKind::Video)
+            .mime_type("audio", AttachmentKind::Audio);
+        for extension in [
+            "png", "jpg", "jpeg", "gif", "webp", "svg", "bmp", "ico", "avif",
+        ]
+        .iter()
+        {
+            table = table.extension(extension, AttachmentKind::Image);
+        }
+        // ogg has always been rendered as a video
+        for extension in ["mp4", "webm", "ogg", "ogv", "mov", "m4v"].iter() {
+            table = table.extension(extension, AttachmentKind::Video);
+        }
+        for extension in ["mp3", "wav", "oga", "opus", "m4a", "aac", "flac"].iter() {
+            table = table.extension(extension, AttachmentKind::Audio);
+        }
+        table
+    }
+}
+
+impl AttachmentTable {
+    /// An empty table, every attachment renders as a download link.
+    pub fn new() -> AttachmentTable {
+        AttachmentTable {
+            extensions: HashMap::new(),
+            mime_types: HashMap::new(),
+        }
+    }
+
+    /// Map a file extension, case insensitive and without the dot.
+    pub fn extension(mut self, extension: &str, kind: AttachmentKind) -> Self {
+        self.extensions.insert(extension.to_lowercase(), kind);
+        self
+    }
+
+    /// Map a full mime type (`application/pdf`) or a top level type (`image`).
+    pub fn mime_type(mut self, mime_type: &str, kind: AttachmentKind) -> Self {
+        self.mime_types.insert(mime_type.to_lowercase(), kind);
+        self
+    }
+
+    pub fn classify(&self, url: &str, mime_type: Option<&str>) -> AttachmentKind {
+        if let Some(mime_type) = mime_type {
+            let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+            let mime_type = mime_type.to_lowercase();
+            let top_level = mime_type.split('/').next().unwrap_or_default();
+            if let Some(kind) = self
+                .mime_types
+                .get(&mime_type)
+                .or_else(|| self.mime_types.get(top_level))
+            {
+                return *kind;
+            }
+        }
+
+        extension(url)
+            .and_then(|extension| self.extensions.get(&extension))
+            .copied()
+            .unwrap_or(AttachmentKind::Download)
+    }
+}
+
+// lowercase extension of the last path segment, ignoring query and fragment
+fn extension(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file_name = path.rsplit('/').next().unwrap_or_default();
+    match file_name.rfind('.') {
+        Some(index) if index + 1 < file_name.len() => Some(file_name[index + 1..].to_lowercase()),
+        _ => None,
+    }
+}
+
+pub(crate) fn file_name(url: &str) -> &str {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    path.rsplit('/').next().unwrap_or_default()
+}
+
+// 1536 => "1.5 KB"
+pub(crate) fn human_size(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, units[unit])
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
@@ -1,5 +1,122 @@
 use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::RenderOptions;
+
+// attributes of a line, carried by its newline
+pub(crate) const BLOCK_ATTRIBUTES: [&str; 7] = [
+    "header",
+    "list",
+    "code-block",
+    "blockquote",
+    "align",
+    "indent",
+    "direction",
+];
+
+// line formats shared by paragraphs, headers and block items
+pub(crate) struct LineFormat {
+    pub(crate) indent: u64,
+    pub(crate) align: String,
+    pub(crate) direction: String,
+}
+
+impl LineFormat {
+    pub(crate) fn from_attr(attr: &Map<String, Value>) -> LineFormat {
+        // every block may has indent
+        let indent = if let Some(Value::Number(indent)) = attr.get("indent") {
+            indent.as_u64().unwrap_or(0u64)
+        } else {
+            0u64
+        };
+        let align = if let Some(Value::String(align)) = attr.get("align") {
+            String::from(align)
+        } else {
+            String::from("")
+        };
+        let direction = if let Some(Value::String(direction)) = attr.get("direction") {
+            String::from(direction)
+        } else {
+            String::from("")
+        };
+
+        LineFormat {
+            indent,
+            align,
+            direction,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.indent == 0 && self.align.is_empty() && self.direction.is_empty()
+    }
+
+    pub(crate) fn write_class_list<W: Write>(
+        &self,
+        out: &mut W,
+        options: &RenderOptions,
+    ) -> fmt::Result {
+        let prefix = &options.class_prefix;
+        let mut separator = "";
+        if self.indent > 0 {
+            write!(out, "{}{}indent-{}", separator, prefix, self.indent)?;
+            separator = " ";
+        }
+        if !self.align.is_empty() {
+            write!(out, "{}{}align-{}", separator, prefix, self.align)?;
+            separator = " ";
+        }
+        if !self.direction.is_empty() {
+            write!(out, "{}{}direction-{}", separator, prefix, self.direction)?;
+        }
+        Ok(())
+    }
+
+    // the declarations of quill's stylesheet for the same classes
+    pub(crate) fn write_style_list<W: Write>(&self, out: &mut W, list_item: bool) -> fmt::Result {
+        if self.indent > 0 {
+            // list items keep room for the bullet
+            let padding = self.indent as f64 * 3.0 + if list_item { 1.5 } else { 0.0 };
+            let side = if self.direction == "rtl" {
+                "right"
+            } else {
+                "left"
+            };
+            write!(out, "padding-{}: {}em; ", side, padding)?;
+        }
+        if !self.align.is_empty() {
+            write!(out, "text-align: {}; ", self.align)?;
+        }
+        if !self.direction.is_empty() {
+            write!(out, "direction: {}; ", self.direction)?;
+            if self.align.is_empty() {
+                out.write_str("text-align: inherit; ")?;
+            }
+        }
+        Ok(())
+    }
+
+    // ` class="..."` or ` style="..."`, or nothing when there is no line format
+    pub(crate) fn write_html_attr<W: Write>(
+        &self,
+        out: &mut W,
+        options: &RenderOptions,
+        list_item: bool,
+    ) -> fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        if options.inline_styles {
+            out.write_str(" style=\"")?;
+            self.write_style_list(out, list_item)?;
+        } else {
+            out.write_str(" class=\"")?;
+            self.write_class_list(out, options)?;
+        }
+        out.write_char('"')
+    }
+}
 
 #[derive(Clone)]
 pub struct BlockTag {
@@ -8,44 +125,88 @@ pub struct BlockTag {
 }
 
 impl BlockTag {
-    fn add_block(&self) -> String {
+    fn write_block<W: Write>(
+        &self,
+        out: &mut W,
+        line_format: &LineFormat,
+        options: &RenderOptions,
+    ) -> fmt::Result {
         match self.block_type {
-            "ordered" | "bullet" => format!("<{}>", self.tag),
-            "code-block" => format!("<{} class=\"ql-syntax\" spellcheck=\"false\">", self.tag),
-            _ => String::from(""),
-        }
-    }
+            "ordered" | "bullet" => write!(out, "<{}>", self.tag),
+            // quill's checklist, one list per run of checked or unchecked items
+            "checked" | "unchecked" => write!(
+                out,
+                "<{} data-checked=\"{}\">",
+                self.tag,
+                self.block_type == "checked"
+            ),
+            "code-block" => {
+                // code block lines have no indent, the pre carries the rest of the first line format
+                let pre_format = LineFormat {
+                    indent: 0,
+                    align: line_format.align.clone(),
+                    direction: line_format.direction.clone(),
+                };
+                let code_block_class = options.code_block_class.as_str();
 
-    fn add_item(&self, content: &String, intent: u64, align: String) -> String {
-        let mut class_list = String::new();
-        if intent > 0 {
-            class_list = format!("ql-indent-{}", intent);
-        }
-
-        if !align.is_empty() {
-            if !class_list.is_empty() {
-                class_list.push(' ');
+                write!(out, "<{}", self.tag)?;
+                if options.inline_styles {
+                    if !code_block_class.is_empty() {
+                        write!(out, " class=\"{}\"", code_block_class)?;
+                    }
+                    pre_format.write_html_attr(out, options, false)?;
+                } else if !code_block_class.is_empty() || !pre_format.is_empty() {
+                    write!(out, " class=\"{}", code_block_class)?;
+                    if !code_block_class.is_empty() && !pre_format.is_empty() {
+                        out.write_char(' ')?;
+                    }
+                    pre_format.write_class_list(out, options)?;
+                    out.write_char('"')?;
+                }
+                out.write_str(" spellcheck=\"false\">")
             }
-            class_list.push_str(&format!("ql-align-{}", align));
+            _ => Ok(()),
         }
+    }
+
+    fn write_item<W: Write, F: FnOnce(&mut W) -> fmt::Result>(
+        &self,
+        out: &mut W,
+        content: F,
+        line_format: &LineFormat,
+        options: &RenderOptions,
+    ) -> fmt::Result {
         match self.block_type {
-            "ordered" | "bullet" => {
-                if class_list.is_empty() {
-                    format!("<li>{}</li>", content)
-                } else {
-                    format!("<li class=\"{}\">{}</li>", class_list, content)
-                }
+            "ordered" | "bullet" | "checked" | "unchecked" => {
+                out.write_str("<li")?;
+                line_format.write_html_attr(out, options, true)?;
+                out.write_char('>')?;
+                content(out)?;
+                out.write_str("</li>")
             }
-            "code-block" => format!("{}\n", content),
-            _ => String::from(""),
+            "code-block" => {
+                content(out)?;
+                out.write_char('\n')
+            }
+            _ => Ok(()),
         }
     }
+
+    fn write_end<W: Write>(&self, out: &mut W) -> fmt::Result {
+        write!(out, "</{}>", self.tag)
+    }
 }
 
 pub struct BlockState {
     block_tag: HashMap<&'static str, BlockTag>,
-    current_block: Option<BlockTag>,
-    indent: u64,
+    // open blocks with their indent, innermost last
+    open_blocks: Vec<(BlockTag, u64)>,
+}
+
+impl Default for BlockState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BlockState {
@@ -65,6 +226,20 @@ impl BlockState {
                 tag: "ul",
             },
         );
+        block_tag.insert(
+            "checked",
+            BlockTag {
+                block_type: "checked",
+                tag: "ul",
+            },
+        );
+        block_tag.insert(
+            "unchecked",
+            BlockTag {
+                block_type: "unchecked",
+                tag: "ul",
+            },
+        );
         block_tag.insert(
             "code-block",
             BlockTag {
@@ -74,104 +249,100 @@ impl BlockState {
         );
 
         BlockState {
-            block_tag: block_tag,
-            current_block: None,
-            indent: 0,
+            block_tag,
+            open_blocks: Vec::new(),
         }
     }
     pub fn open_block(
         &mut self,
         attr: &Map<String, Value>,
-        block_type: &String,
-        content: &String,
+        block_type: &str,
+        content: &str,
     ) -> String {
-        let mut pending = String::from("");
+        self.open_block_with_options(attr, block_type, content, &RenderOptions::default())
+    }
 
-        // block may has indent
-        let indent = if let Some(Value::Number(indent)) = attr.get("indent") {
-            indent.as_u64().unwrap_or(0u64)
-        } else {
-            0u64
-        };
+    pub fn open_block_with_options(
+        &mut self,
+        attr: &Map<String, Value>,
+        block_type: &str,
+        content: &str,
+        options: &RenderOptions,
+    ) -> String {
+        let mut pending = String::from("");
+        // writing into a String never fails
+        self.write_open_block(
+            &mut pending,
+            attr,
+            block_type,
+            |out| out.write_str(content),
+            options,
+        )
+        .unwrap();
+        pending
+    }
 
-        let align = if let Some(Value::String(align)) = attr.get("align") {
-            String::from(align)
-        } else {
-            String::from("")
-        };
+    pub(crate) fn write_open_block<W: Write, F: FnOnce(&mut W) -> fmt::Result>(
+        &mut self,
+        out: &mut W,
+        attr: &Map<String, Value>,
+        block_type: &str,
+        content: F,
+        options: &RenderOptions,
+    ) -> fmt::Result {
+        let line_format = LineFormat::from_attr(attr);
+        let indent = line_format.indent;
 
-        println!("indent: {}", indent);
+        if let Some(target_block) = self.block_tag.get(block_type) {
+            // close the deeper blocks, and a block of another type at the same indent
+            while let Some((open_block, open_indent)) = self.open_blocks.last() {
+                let same_level = *open_indent == indent;
+                if *open_indent < indent
+                    || (same_level && open_block.block_type == target_block.block_type)
+                {
+                    break;
+                }
+                open_block.write_end(out)?;
+                self.open_blocks.pop();
+            }
 
-        if let Some(target_block) = self.block_tag.get(block_type.as_str()) {
-            // we are in a list block
-            if let Some(current_block) = &self.current_block {
+            match self.open_blocks.last() {
                 // block type not change, just pend block item into it
-                if current_block.block_type == target_block.block_type && indent == self.indent {
-                    pending = current_block.add_item(content, indent, align);
-                } else if current_block.block_type != target_block.block_type {
-                    // wo get a new block with different type, need close the last block first
-                    let end_block = format!("</{}>", current_block.tag);
-                    let end_blocks = (0..self.indent - indent + 1)
-                        .map(|_| end_block.clone())
-                        .collect::<String>();
-                    let result = format!(
-                        "{}{}{}",
-                        end_blocks,
-                        target_block.add_block(),
-                        target_block.add_item(content, indent, align)
-                    );
-                    self.current_block = Some(target_block.clone());
-                    self.indent = indent;
-
-                    pending = result;
-                } else if indent > self.indent {
-                    // indent increase, open a new block
-                    pending = format!(
-                        "{}{}",
-                        target_block.add_block(),
-                        target_block.add_item(content, indent, align)
-                    );
-                    self.current_block = Some(target_block.clone());
-                    self.indent = indent;
-                } else {
-                    let end_block = format!("</{}>", current_block.tag);
-                    let end_blocks = (0..self.indent - indent)
-                        .map(|_| end_block.clone())
-                        .collect::<String>();
-                    pending = format!(
-                        "{}{}",
-                        end_blocks,
-                        target_block.add_item(content, indent, align)
-                    );
-                    self.current_block = Some(target_block.clone());
-                    self.indent = indent;
+                Some((open_block, open_indent)) if *open_indent == indent => {
+                    open_block.write_item(out, content, &line_format, options)?;
+                }
+                // a new block, nested in the open one when it is deeper
+                _ => {
+                    target_block.write_block(out, &line_format, options)?;
+                    target_block.write_item(out, content, &line_format, options)?;
+                    self.open_blocks.push((target_block.clone(), indent));
                 }
-            } else {
-                // a totally new list block
-                let result = format!(
-                    "{}{}",
-                    target_block.add_block(),
-                    target_block.add_item(content, indent, align)
-                );
-                self.current_block = Some(target_block.clone());
-                self.indent = indent;
-
-                pending = result;
             }
         }
-        pending
+        Ok(())
     }
 
     pub fn check_and_close_current_block(&mut self) -> String {
         let mut pending = String::from("");
-        if let Some(current_block) = &self.current_block {
-            let end_block = format!("</{}>", current_block.tag);
-            let end_blocks = (0..self.indent + 1)
-                .map(|_| end_block.clone())
-                .collect::<String>();
-            pending = format!("{}", end_blocks);
-            self.current_block = None;
-        }
+        self.write_close_current_block(&mut pending).unwrap();
         pending
     }
+
+    pub(crate) fn write_close_current_block<W: Write>(&mut self, out: &mut W) -> fmt::Result {
+        while let Some((open_block, _)) = self.open_blocks.pop() {
+            open_block.write_end(out)?;
+        }
+        Ok(())
+    }
+}
+
+// a line is only rendered as a header when it is neither a list item, code nor a quote
+pub(crate) fn header_level(attr: &Map<String, Value>) -> Option<u64> {
+    if let Some(Value::String(_)) = attr.get("list") {
+        return None;
+    }
+    if attr.get("code-block").is_some() || attr.get("blockquote").is_some() {
+        return None;
+    }
+    attr.get("header").and_then(|v| v.as_u64())
 }
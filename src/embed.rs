@@ -0,0 +1,207 @@
+use serde_json::{Map, Value};
+use std::fmt::{self, Write};
+
+use crate::asset::AssetKind;
+use crate::attachment::{file_name, human_size, AttachmentKind};
+use crate::inline_format;
+use crate::mention;
+use crate::RenderOptions;
+
+// embed inserts, `{"savvy_image": "path/to/image"}`, `{"mention": {...}}`...
+pub(crate) enum Embed<'a> {
+    Image(&'a str),
+    SavvyImage(&'a str),
+    SavvyAttach(&'a str),
+    Mention(&'a Map<String, Value>),
+    Video(&'a str),
+    Formula(&'a str),
+}
+
+impl<'a> Embed<'a> {
+    pub(crate) fn from_insert(insert: &'a Map<String, Value>) -> Option<Embed<'a>> {
+        if let Some(Value::String(image)) = insert.get("image") {
+            Some(Embed::Image(image))
+        } else if let Some(Value::String(savvy_image)) = insert.get("savvy_image") {
+            Some(Embed::SavvyImage(savvy_image))
+        } else if let Some(Value::String(savvy_attach)) = insert.get("savvy_attach") {
+            Some(Embed::SavvyAttach(savvy_attach))
+        } else if let Some(Value::Object(mention)) = insert.get("mention") {
+            Some(Embed::Mention(mention))
+        } else if let Some(Value::String(video)) = insert.get("video") {
+            Some(Embed::Video(video))
+        } else if let Some(Value::String(formula)) = insert.get("formula") {
+            Some(Embed::Formula(formula))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn asset(&self) -> Option<(AssetKind, &'a str)> {
+        match self {
+            Embed::Image(url) => Some((AssetKind::Image, url)),
+            Embed::SavvyImage(url) => Some((AssetKind::SavvyImage, url)),
+            Embed::SavvyAttach(url) => Some((AssetKind::SavvyAttach, url)),
+            Embed::Video(url) => Some((AssetKind::Video, url)),
+            Embed::Mention(_) | Embed::Formula(_) => None,
+        }
+    }
+}
+
+pub(crate) fn format(embed: &Embed, attr: &Option<Value>, options: &RenderOptions) -> String {
+    let tmp_alt = attr_str(attr, "alt").unwrap_or_default();
+
+    // asset urls go through the rewriter, dropping the embed when it returns None
+    let url = match (embed.asset(), &options.url_rewriter) {
+        (Some((kind, url)), Some(url_rewriter)) => {
+            let empty = Map::new();
+            let attributes = match attr {
+                Some(Value::Object(attr)) => attr,
+                _ => &empty,
+            };
+            match url_rewriter(kind, url, attributes) {
+                Some(url) => url,
+                None => return String::from(""),
+            }
+        }
+        (Some((_, url)), None) => String::from(url),
+        (None, _) => String::from(""),
+    };
+
+    match embed {
+        Embed::Image(_) => image_tag(&url, attr, false, options),
+        Embed::SavvyImage(_) => image_tag(&url, attr, true, options),
+        Embed::SavvyAttach(savvy_attach) => {
+            let mime_type = attr_str(attr, "type");
+            match options.attachment_table.classify(savvy_attach, mime_type) {
+                AttachmentKind::Image => image_tag(&url, attr, true, options),
+                AttachmentKind::Video => format!(
+                    "<video src=\"{}\" alt=\"{}\" controls></video>",
+                    escape_attr(&url),
+                    escape_attr(tmp_alt)
+                ),
+                AttachmentKind::Audio => {
+                    format!("<audio src=\"{}\" controls></audio>", escape_attr(&url))
+                }
+                AttachmentKind::Download => {
+                    let name = attr_str(attr, "name")
+                        .or_else(|| attr_str(attr, "alt"))
+                        .unwrap_or_else(|| file_name(savvy_attach));
+                    let size = match attr {
+                        Some(Value::Object(attr)) => match attr.get("size") {
+                            Some(Value::Number(size)) => size.as_u64().map(human_size),
+                            Some(Value::String(size)) => Some(size.clone()),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    let label = match size {
+                        Some(size) => format!("{} ({})", name, size),
+                        None => String::from(name),
+                    };
+                    format!(
+                        "<a href=\"{}\" download=\"{}\">{}</a>",
+                        escape_attr(&url),
+                        escape_attr(name),
+                        escape_attr(&label)
+                    )
+                }
+            }
+        }
+        Embed::Mention(mention) => mention::format(mention, options),
+        Embed::Video(_) => {
+            // "<iframe class=\"ql-video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"https://media.w3.org/2010/05/sintel/trailer.mp4\"></iframe>"
+            format!(
+                "<iframe class=\"{}video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"{}\"></iframe>",
+                options.class_prefix,
+                escape_attr(&url)
+            )
+        }
+        Embed::Formula(formula) => {
+            // the tex source is kept in data-value, the renderer hook fills the span
+            let rendered = match &options.formula_renderer {
+                Some(formula_renderer) => formula_renderer(formula),
+                None => String::from(""),
+            };
+            format!(
+                "<span class=\"{}formula\" data-value=\"{}\">{}</span>",
+                options.class_prefix,
+                escape_attr(formula),
+                rendered
+            )
+        }
+    }
+}
+
+// `<img>` with the attributes of quill's image blot and the image-resize module,
+// wrapped in a link when the embed has one. Savvy images always carry an alt and
+// otherwise render the same attributes as core images, so core images also get the
+// `{prefix}image` class quill's blot does not write, the only way `html_to_delta` can
+// tell a core image with an alt from a savvy image
+fn image_tag(src: &str, attr: &Option<Value>, savvy: bool, options: &RenderOptions) -> String {
+    let mut tag = if savvy {
+        format!("<img src=\"{}\"", escape_attr(src))
+    } else {
+        format!(
+            "<img class=\"{}image\" src=\"{}\"",
+            options.class_prefix,
+            escape_attr(src)
+        )
+    };
+    let attr = match attr {
+        Some(Value::Object(attr)) => Some(attr),
+        _ => None,
+    };
+
+    for key in ["alt", "width", "height", "style"].iter() {
+        let value = attr.and_then(|attr| match attr.get(*key) {
+            Some(Value::String(value)) => Some(value.clone()),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        });
+        match value {
+            Some(value) => tag.push_str(&format!(" {}=\"{}\"", key, escape_attr(&value))),
+            None if *key == "alt" && savvy => tag.push_str(" alt=\"\""),
+            None => (),
+        }
+    }
+    tag.push('>');
+
+    match attr
+        .and_then(|attr| attr.get("link"))
+        .and_then(|v| v.as_str())
+    {
+        Some(href) => inline_format::link(tag, href, options),
+        None => tag,
+    }
+}
+
+fn attr_str<'a>(attr: &'a Option<Value>, key: &str) -> Option<&'a str> {
+    match attr {
+        Some(Value::Object(attr)) => attr.get(key).and_then(|v| v.as_str()),
+        _ => None,
+    }
+}
+
+pub(crate) fn escape_attr(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    // writing into a String never fails
+    write_escaped_attr(&mut escaped, value).unwrap();
+    escaped
+}
+
+pub(crate) fn write_escaped_attr<W: Write>(out: &mut W, value: &str) -> fmt::Result {
+    let mut start = 0;
+    for (index, char) in value.char_indices() {
+        let escaped = match char {
+            '&' => "&amp;",
+            '"' => "&quot;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            _ => continue,
+        };
+        out.write_str(&value[start..index])?;
+        out.write_str(escaped)?;
+        start = index + 1;
+    }
+    out.write_str(&value[start..])
+}
@@ -0,0 +1,715 @@
+use serde_json::{Map, Value};
+
+use crate::attachment::file_name;
+use crate::normalize::normalize;
+use crate::{DeltaOp, RenderOptions};
+
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// blocks whose end is the end of a line
+const LINE_ELEMENTS: [&str; 25] = [
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "li",
+    "pre",
+    "blockquote",
+    "section",
+    "article",
+    "header",
+    "footer",
+    "aside",
+    "nav",
+    "main",
+    "figure",
+    "figcaption",
+    "address",
+    "dt",
+    "dd",
+    "td",
+    "th",
+];
+
+// blocks only holding other blocks
+const CONTAINER_ELEMENTS: [&str; 8] = ["ul", "ol", "dl", "table", "thead", "tbody", "tfoot", "tr"];
+
+enum Token {
+    Start {
+        name: String,
+        attrs: Vec<(String, String)>,
+    },
+    End(String),
+    Text(String),
+}
+
+/// The delta of an html document, the inverse of `parser` for the markup it renders
+/// with the default options, in the form of `normalize`.
+///
+/// An `<img>` with an `alt` attribute and without the `ql-image` class of core images
+/// becomes a `savvy_image`, as savvy images always carry one.
+pub fn html_to_delta(html: &str) -> Vec<DeltaOp> {
+    html_to_delta_with_options(html, &RenderOptions::default())
+}
+
+/// The inverse of `render` with the same options, reading the classes with their
+/// `class_prefix`.
+pub fn html_to_delta_with_options(html: &str, options: &RenderOptions) -> Vec<DeltaOp> {
+    let mut converter = Converter {
+        class_prefix: &options.class_prefix,
+        ops: Vec::new(),
+        stack: Vec::new(),
+        newlines: 0,
+        line_started: false,
+        soft_space: None,
+        skip: 0,
+    };
+    for token in tokenize(html) {
+        match token {
+            Token::Start { name, attrs } => converter.start(&name, &attrs),
+            Token::End(name) => converter.end(&name),
+            Token::Text(text) => converter.text(&text),
+        }
+    }
+    converter.end_all();
+    normalize(&converter.ops)
+}
+
+// an open element
+struct Element {
+    name: String,
+    // inline formats of its content
+    inline: Map<String, Value>,
+    // line formats of the lines ending inside of it, None for inline elements and containers
+    block: Option<Map<String, Value>>,
+    // newlines before it opened, it ends a line of its own only when none followed
+    newlines: usize,
+    // list format of the items of a `<ul>` or `<ol>`
+    list: Option<&'static str>,
+}
+
+struct Converter<'a> {
+    class_prefix: &'a str,
+    ops: Vec<DeltaOp>,
+    stack: Vec<Element>,
+    newlines: usize,
+    // text or embeds since the last newline
+    line_started: bool,
+    // whitespace with a line break outside of `pre`, a single space with the formats where it
+    // was written if more content follows
+    soft_space: Option<Map<String, Value>>,
+    // depth inside an element whose content is already part of an embed
+    skip: usize,
+}
+
+impl<'a> Converter<'a> {
+    // the rest of a class after the prefix and the name, "ql-indent-2" => "2"
+    fn class_name<'c>(&self, class: &'c str, name: &str) -> Option<&'c str> {
+        class.strip_prefix(self.class_prefix)?.strip_prefix(name)
+    }
+
+    fn inline_attributes(&self) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        for element in self.stack.iter() {
+            for (key, value) in element.inline.iter() {
+                attributes.insert(key.clone(), value.clone());
+            }
+        }
+        attributes
+    }
+
+    fn block_attributes(&self) -> Map<String, Value> {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|element| element.block.clone())
+            .unwrap_or_default()
+    }
+
+    fn in_pre(&self) -> bool {
+        self.stack.iter().any(|element| element.name == "pre")
+    }
+
+    fn insert(&mut self, insert: Value, attributes: Map<String, Value>) {
+        if let Some(soft_space) = self.soft_space.take() {
+            if self.line_started {
+                self.push(Value::String(String::from(" ")), soft_space);
+            }
+        }
+        let mut inline = self.inline_attributes();
+        inline.extend(attributes);
+        self.push(insert, inline);
+        self.line_started = true;
+    }
+
+    fn newline(&mut self, attributes: Map<String, Value>) {
+        self.push(Value::String(String::from("\n")), attributes);
+        self.newlines += 1;
+        self.line_started = false;
+        self.soft_space = None;
+    }
+
+    fn flush_line(&mut self) {
+        if self.line_started {
+            self.newline(self.block_attributes());
+        }
+    }
+
+    fn push(&mut self, insert: Value, attributes: Map<String, Value>) {
+        let attributes = if attributes.is_empty() {
+            None
+        } else {
+            Some(Value::Object(attributes))
+        };
+        self.ops.push(DeltaOp { insert, attributes });
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.skip > 0 {
+            return;
+        }
+        // zero width cursor and mention guards
+        let text = text.replace('\u{feff}', "");
+        if self.in_pre() {
+            for segment in text.split_inclusive('\n') {
+                match segment.strip_suffix('\n') {
+                    Some(line) => {
+                        self.insert_text(line.trim_end_matches('\r'));
+                        self.newline(self.block_attributes());
+                    }
+                    None => self.insert_text(segment),
+                }
+            }
+            return;
+        }
+
+        // html formatting whitespace, anything with a line break, collapses
+        let mut literal = String::new();
+        let mut whitespace = String::new();
+        for char in text.chars() {
+            if char.is_ascii_whitespace() {
+                whitespace.push(char);
+                continue;
+            }
+            self.whitespace(&mut literal, &whitespace);
+            whitespace.clear();
+            literal.push(char);
+        }
+        self.whitespace(&mut literal, &whitespace);
+        self.insert_text(&literal);
+    }
+
+    fn whitespace(&mut self, literal: &mut String, whitespace: &str) {
+        if whitespace.contains(['\n', '\r']) {
+            self.insert_text(literal);
+            literal.clear();
+            if self.soft_space.is_none() {
+                self.soft_space = Some(self.inline_attributes());
+            }
+        } else {
+            literal.push_str(whitespace);
+        }
+    }
+
+    fn insert_text(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.insert(Value::String(String::from(text)), Map::new());
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &[(String, String)]) {
+        let void = VOID_ELEMENTS.contains(&name);
+        if self.skip > 0 {
+            if !void {
+                self.skip += 1;
+            }
+            return;
+        }
+
+        if let Some((insert, attributes)) = embed(name, attrs, self.class_prefix) {
+            self.insert(insert, attributes);
+            if !void {
+                self.skip = 1;
+            }
+            return;
+        }
+        if name == "br" {
+            self.newline(self.block_attributes());
+            return;
+        }
+        if name == "hr" {
+            self.flush_line();
+            return;
+        }
+        if void {
+            return;
+        }
+
+        let line_element = LINE_ELEMENTS.contains(&name);
+        let container = CONTAINER_ELEMENTS.contains(&name);
+        if line_element || container {
+            self.close_implied(name);
+            self.flush_line();
+        }
+
+        let block = if line_element {
+            let mut block = self.block_attributes();
+            block.extend(self.line_attributes(name, attrs));
+            Some(block)
+        } else {
+            None
+        };
+        let inline = if line_element || container {
+            Map::new()
+        } else {
+            self.inline_format(name, attrs)
+        };
+        let list = match (name, attr(attrs, "data-checked")) {
+            ("ol", _) => Some("ordered"),
+            ("ul", Some("true")) => Some("checked"),
+            ("ul", Some("false")) => Some("unchecked"),
+            ("ul", _) => Some("bullet"),
+            _ => None,
+        };
+        self.stack.push(Element {
+            name: String::from(name),
+            inline,
+            block,
+            newlines: self.newlines,
+            list,
+        });
+    }
+
+    // `<p>` ends at the next block, `<li>` at the next item of its list
+    fn close_implied(&mut self, name: &str) {
+        let implied = if name == "li" {
+            self.stack
+                .iter()
+                .rposition(|element| ["ul", "ol", "li"].contains(&element.name.as_str()))
+                .filter(|position| self.stack[*position].name == "li")
+        } else {
+            self.stack
+                .iter()
+                .rposition(|element| element.block.is_some())
+                .filter(|position| self.stack[*position].name == "p")
+        };
+        if let Some(position) = implied {
+            self.close_to(position);
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        if let Some(position) = self.stack.iter().rposition(|element| element.name == name) {
+            self.close_to(position);
+        }
+    }
+
+    fn end_all(&mut self) {
+        self.skip = 0;
+        self.close_to(0);
+        self.flush_line();
+    }
+
+    fn close_to(&mut self, position: usize) {
+        while self.stack.len() > position {
+            let element = self.stack.pop().unwrap();
+            if let Some(block) = element.block {
+                // an empty paragraph is still a line
+                if self.line_started || self.newlines == element.newlines {
+                    self.newline(block);
+                }
+            } else if CONTAINER_ELEMENTS.contains(&element.name.as_str()) {
+                self.flush_line();
+            }
+        }
+    }
+
+    // the line formats an element adds to the lines inside of it
+    fn line_attributes(&self, name: &str, attrs: &[(String, String)]) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level: u64 = name[1..].parse().unwrap_or(1);
+                attributes.insert(String::from("header"), Value::from(level));
+            }
+            "li" => {
+                let lists: Vec<&str> = self
+                    .stack
+                    .iter()
+                    .filter_map(|element| element.list)
+                    .collect();
+                let list_type = attr(attrs, "data-list")
+                    .or_else(|| lists.last().copied())
+                    .unwrap_or("bullet");
+                attributes.insert(String::from("list"), Value::from(list_type));
+                if lists.len() > 1 {
+                    attributes.insert(String::from("indent"), Value::from(lists.len() - 1));
+                }
+            }
+            "pre" => {
+                attributes.insert(String::from("code-block"), Value::Bool(true));
+            }
+            "blockquote" => {
+                attributes.insert(String::from("blockquote"), Value::Bool(true));
+            }
+            _ => (),
+        }
+
+        for class in attr(attrs, "class").unwrap_or_default().split_whitespace() {
+            if let Some(indent) = self.class_name(class, "indent-") {
+                if let Ok(indent) = indent.parse::<u64>() {
+                    attributes.insert(String::from("indent"), Value::from(indent));
+                }
+            } else if let Some(align) = self.class_name(class, "align-") {
+                attributes.insert(String::from("align"), Value::from(align));
+            } else if let Some(direction) = self.class_name(class, "direction-") {
+                attributes.insert(String::from("direction"), Value::from(direction));
+            }
+        }
+        for (property, value) in styles(attrs) {
+            match (property.as_str(), value.as_str()) {
+                ("text-align", "inherit") => (),
+                ("text-align", align) => {
+                    attributes.insert(String::from("align"), Value::from(align));
+                }
+                ("direction", "rtl") => {
+                    attributes.insert(String::from("direction"), Value::from("rtl"));
+                }
+                _ => (),
+            }
+        }
+        attributes
+    }
+
+    fn inline_format(&self, name: &str, attrs: &[(String, String)]) -> Map<String, Value> {
+        let mut attributes = Map::new();
+        let mark = match name {
+            "strong" | "b" => Some(("bold", Value::Bool(true))),
+            "em" | "i" => Some(("italic", Value::Bool(true))),
+            "u" => Some(("underline", Value::Bool(true))),
+            "s" | "strike" | "del" => Some(("strike", Value::Bool(true))),
+            // code blocks are often written as `<pre><code>`
+            "code" if !self.in_pre() => Some(("code", Value::Bool(true))),
+            "sup" => Some(("script", Value::from("super"))),
+            "sub" => Some(("script", Value::from("sub"))),
+            "a" => attr(attrs, "href").map(|href| ("link", Value::from(href))),
+            _ => None,
+        };
+        if let Some((key, value)) = mark {
+            attributes.insert(String::from(key), value);
+        }
+
+        for class in attr(attrs, "class").unwrap_or_default().split_whitespace() {
+            if let Some(size) = self.class_name(class, "size-") {
+                attributes.insert(String::from("size"), Value::from(size));
+            } else if let Some(font) = self.class_name(class, "font-") {
+                attributes.insert(String::from("font"), Value::from(font));
+            }
+        }
+        for (property, value) in styles(attrs) {
+            let key = match property.as_str() {
+                "color" => "color",
+                "background-color" | "background" => "background",
+                "font-size" => "size",
+                "font-family" => "font",
+                _ => continue,
+            };
+            attributes.insert(String::from(key), Value::from(value));
+        }
+        attributes
+    }
+}
+
+// the embed an element stands for, with its content left out
+fn embed(
+    name: &str,
+    attrs: &[(String, String)],
+    class_prefix: &str,
+) -> Option<(Value, Map<String, Value>)> {
+    let mut insert = Map::new();
+    let mut attributes = Map::new();
+    let classes: Vec<&str> = attr(attrs, "class")
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect();
+    let has_class = |name: &str| {
+        classes
+            .iter()
+            .any(|class| class.strip_prefix(class_prefix) == Some(name))
+    };
+
+    match name {
+        "img" => {
+            let src = attr(attrs, "src")?;
+            let key = if attr(attrs, "alt").is_some() && !has_class("image") {
+                "savvy_image"
+            } else {
+                "image"
+            };
+            insert.insert(String::from(key), Value::from(src));
+            for key in ["alt", "width", "height", "style"].iter() {
+                if let Some(value) = attr(attrs, key).filter(|value| !value.is_empty()) {
+                    attributes.insert(String::from(*key), Value::from(value));
+                }
+            }
+        }
+        "iframe" => {
+            insert.insert(String::from("video"), Value::from(attr(attrs, "src")?));
+        }
+        "video" | "audio" => {
+            insert.insert(
+                String::from("savvy_attach"),
+                Value::from(attr(attrs, "src")?),
+            );
+            if let Some(alt) = attr(attrs, "alt").filter(|alt| !alt.is_empty()) {
+                attributes.insert(String::from("alt"), Value::from(alt));
+            }
+        }
+        "a" if attr(attrs, "download").is_some() => {
+            let href = attr(attrs, "href")?;
+            insert.insert(String::from("savvy_attach"), Value::from(href));
+            if let Some(name) = attr(attrs, "download").filter(|name| *name != file_name(href)) {
+                attributes.insert(String::from("name"), Value::from(name));
+            }
+        }
+        "span" | "a" if classes.contains(&"mention") => {
+            let mut mention = Map::new();
+            for (key, value) in attrs.iter() {
+                if let Some(key) = key.strip_prefix("data-") {
+                    if !value.is_empty() {
+                        mention.insert(camel_case(key), Value::from(value.as_str()));
+                    }
+                }
+            }
+            insert.insert(String::from("mention"), Value::Object(mention));
+        }
+        "span" if has_class("formula") => {
+            let formula = attr(attrs, "data-value")?;
+            insert.insert(String::from("formula"), Value::from(formula));
+        }
+        _ => return None,
+    }
+    Some((Value::Object(insert), attributes))
+}
+
+fn attr<'a>(attrs: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.as_str())
+}
+
+// `color: red; font-size: 18px` => [("color", "red"), ("font-size", "18px")]
+fn styles(attrs: &[(String, String)]) -> Vec<(String, String)> {
+    attr(attrs, "style")
+        .unwrap_or_default()
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .map(|(property, value)| {
+            (
+                property.trim().to_ascii_lowercase(),
+                String::from(value.trim()),
+            )
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
+}
+
+// "denotation-char" => "denotationChar"
+fn camel_case(key: &str) -> String {
+    let mut result = String::new();
+    let mut upper = false;
+    for char in key.chars() {
+        if char == '-' {
+            upper = true;
+        } else if upper {
+            result.extend(char.to_uppercase());
+            upper = false;
+        } else {
+            result.push(char);
+        }
+    }
+    result
+}
+
+fn tokenize(html: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = match comment.find("-->") {
+                Some(end) => &comment[end + 3..],
+                None => "",
+            };
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            // doctype and processing instructions
+            rest = match rest.find('>') {
+                Some(end) => &rest[end + 1..],
+                None => "",
+            };
+        } else if rest.starts_with("</") && starts_with_letter(&rest[2..]) {
+            let end = rest.find('>').unwrap_or(rest.len());
+            let name = rest[2..end]
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            tokens.push(Token::End(name));
+            rest = &rest[(end + 1).min(rest.len())..];
+        } else if rest.starts_with('<') && starts_with_letter(&rest[1..]) {
+            let (name, attrs, self_closing, remaining) = start_tag(&rest[1..]);
+            rest = remaining;
+            if name == "script" || name == "style" {
+                // raw text, never content
+                let close = format!("</{}", name);
+                rest = match rest.to_ascii_lowercase().find(&close) {
+                    Some(end) => &rest[end..],
+                    None => "",
+                };
+                continue;
+            }
+            let void = VOID_ELEMENTS.contains(&name.as_str());
+            tokens.push(Token::Start {
+                name: name.clone(),
+                attrs,
+            });
+            if self_closing && !void {
+                tokens.push(Token::End(name));
+            }
+        } else {
+            // a stray `<` is text, the next tag starts after it
+            let first = rest.chars().next().map_or(1, char::len_utf8);
+            let end = rest[first..]
+                .find('<')
+                .map_or(rest.len(), |end| end + first);
+            tokens.push(Token::Text(decode(&rest[..end])));
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+fn starts_with_letter(input: &str) -> bool {
+    input.starts_with(|char: char| char.is_ascii_alphabetic())
+}
+
+// name, attributes, `/>` and the input after the tag
+fn start_tag(input: &str) -> (String, Vec<(String, String)>, bool, &str) {
+    let name_end = input
+        .find(|char: char| char.is_ascii_whitespace() || char == '>' || char == '/')
+        .unwrap_or(input.len());
+    let name = input[..name_end].to_ascii_lowercase();
+    let mut rest = &input[name_end..];
+    let mut attrs = Vec::new();
+    let mut self_closing = false;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        } else if let Some(remaining) = rest.strip_prefix('>') {
+            rest = remaining;
+            break;
+        } else if let Some(remaining) = rest.strip_prefix("/>") {
+            self_closing = true;
+            rest = remaining;
+            break;
+        }
+
+        let key_end = rest
+            .find(|char: char| char.is_ascii_whitespace() || ['=', '>', '/'].contains(&char))
+            .unwrap_or(rest.len());
+        if key_end == 0 {
+            // a stray `/` or `=`
+            rest = &rest[1..];
+            continue;
+        }
+        let key = rest[..key_end].to_ascii_lowercase();
+        rest = rest[key_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(remaining) = rest.strip_prefix('=') {
+            let remaining = remaining.trim_start();
+            let (raw, remaining) = match remaining.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => {
+                    let quoted = &remaining[1..];
+                    match quoted.find(quote) {
+                        Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                        None => (quoted, ""),
+                    }
+                }
+                _ => {
+                    let end = remaining
+                        .find(|char: char| char.is_ascii_whitespace() || char == '>')
+                        .unwrap_or(remaining.len());
+                    (&remaining[..end], &remaining[end..])
+                }
+            };
+            value = decode(raw);
+            rest = remaining;
+        }
+        attrs.push((key, value));
+    }
+    (name, attrs, self_closing, rest)
+}
+
+// character references, an unknown one is kept as written
+fn decode(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end]);
+        match reference.and_then(character) {
+            Some(char) => {
+                decoded.push(char);
+                rest = &rest[reference.unwrap_or_default().len() + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn character(reference: &str) -> Option<char> {
+    match reference {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let code = if let Some(hex) = reference
+                .strip_prefix("#x")
+                .or_else(|| reference.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok()
+            } else if let Some(decimal) = reference.strip_prefix('#') {
+                decimal.parse().ok()
+            } else {
+                None
+            };
+            code.and_then(char::from_u32)
+        }
+    }
+}
@@ -1,127 +1,373 @@
 use serde_json::Value;
+use std::fmt::{self, Write};
+
+use crate::embed::write_escaped_attr;
+use crate::options::{Attributor, LinkPolicy};
+use crate::resolve::LinkResolution;
+use crate::RenderOptions;
 
 // inline format
+#[derive(PartialEq)]
 struct FormatTag {
     key: &'static str,
     tag: &'static str,
     context: Option<String>,
+    link: Option<LinkAttributes>,
 }
 
-impl FormatTag {
-    fn format(&self, input: String, style_input: Option<String>) -> String {
-        if self.key == "a" {
-            let defaut_href = String::from("");
-            let href = self.context.as_ref().unwrap_or(&defaut_href);
+// attributes of an `a` besides the href, from the link policy and the resolver
+#[derive(PartialEq)]
+struct LinkAttributes {
+    policy: LinkPolicy,
+    extra: Vec<(String, String)>,
+}
 
-            if style_input != None {
-                format!("<a href=\"{}\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"{}\" style=\"{}\">{}</a>", href, href, style_input.unwrap_or_default(), input)
-            } else {
-                format!("<a href=\"{}\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"{}\">{}</a>", href, href, input)
+impl FormatTag {
+    fn write_open<W: Write>(
+        &self,
+        out: &mut W,
+        class_input: &str,
+        style_input: &str,
+    ) -> fmt::Result {
+        write!(out, "<{}", self.tag)?;
+        if let Some(link) = &self.link {
+            let href = self.context.as_deref().unwrap_or("");
+            out.write_str(" href=\"")?;
+            write_escaped_attr(out, href)?;
+            out.write_char('"')?;
+            if let Some(rel) = &link.policy.rel {
+                out.write_str(" rel=\"")?;
+                write_escaped_attr(out, rel)?;
+                out.write_char('"')?;
             }
-        } else {
-            if style_input != None {
-                format!(
-                    "<{} style=\"{}\">{}</{}>",
-                    self.tag,
-                    style_input.unwrap_or_default(),
-                    input,
-                    self.tag
-                )
-            } else {
-                format!("<{}>{}</{}>", self.tag, input, self.tag)
+            if let Some(target) = &link.policy.target {
+                out.write_str(" target=\"")?;
+                write_escaped_attr(out, target)?;
+                out.write_char('"')?;
+            }
+            if link.policy.title {
+                out.write_str(" title=\"")?;
+                write_escaped_attr(out, href)?;
+                out.write_char('"')?;
+            }
+            for (key, value) in link.extra.iter() {
+                write!(out, " {}=\"", key)?;
+                write_escaped_attr(out, value)?;
+                out.write_char('"')?;
             }
         }
+        if !class_input.is_empty() {
+            write!(out, " class=\"{}\"", class_input)?;
+        }
+        if !style_input.is_empty() {
+            write!(out, " style=\"{}\"", style_input)?;
+        }
+        out.write_char('>')
+    }
+
+    fn write_close<W: Write>(&self, out: &mut W) -> fmt::Result {
+        write!(out, "</{}>", self.tag)
     }
 }
-pub fn format(mut raw_input: String, attr: &Option<Value>) -> String {
-    if let Some(Value::Object(inner_attr)) = attr {
-        let mut styled_attrs_str = String::from("");
-        let mut formatters: Vec<FormatTag> = Vec::new();
-        for (key, value) in inner_attr {
-            match key.as_str() {
-                "link" => {
-                    formatters.push(FormatTag {
-                        key: "a",
-                        tag: "a",
-                        context: Some(String::from(value.as_str().unwrap_or_default())),
-                    });
-                }
-                "underline" => {
-                    formatters.push(FormatTag {
-                        key: "underline",
-                        tag: "u",
-                        context: None,
-                    });
-                }
-                "strike" => {
-                    formatters.push(FormatTag {
-                        key: "strike",
-                        tag: "s",
-                        context: None,
-                    });
-                }
-                "italic" => {
-                    formatters.push(FormatTag {
-                        key: "italic",
-                        tag: "em",
-                        context: None,
-                    });
-                }
-                "bold" => {
-                    formatters.push(FormatTag {
-                        key: "bold",
-                        tag: "strong",
-                        context: None,
-                    });
-                }
-                "code" => {
-                    formatters.push(FormatTag {
-                        key: "code",
-                        tag: "code",
-                        context: None,
-                    });
-                }
-                "color" => {
-                    styled_attrs_str
-                        .push_str(&format!("color: {}; ", value.as_str().unwrap_or_default()));
-                }
-                "background" => {
-                    styled_attrs_str.push_str(&format!(
-                        "background-color: {}; ",
-                        value.as_str().unwrap_or_default()
-                    ));
-                }
-                "size" => {
-                    styled_attrs_str.push_str(&format!(
-                        "font-size: {}; ",
-                        value.as_str().unwrap_or_default()
-                    ));
-                }
-                "font" => {
-                    styled_attrs_str.push_str(&format!(
-                        "font-family: {}; ",
-                        value.as_str().unwrap_or_default()
-                    ));
+
+// nesting of the marks from outermost to innermost, quill's `Inline.order` with the
+// link outside of code
+const MARK_ORDER: [&str; 7] = [
+    "a",
+    "code",
+    "script",
+    "bold",
+    "italic",
+    "strike",
+    "underline",
+];
+
+fn is_class_name(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// css values of quill's named sizes and fonts, from its stylesheet
+fn named_value<'a>(key: &str, value: &'a str) -> &'a str {
+    match (key, value) {
+        ("size", "small") => "0.75em",
+        ("size", "large") => "1.5em",
+        ("size", "huge") => "2.5em",
+        ("font", "sans-serif") => "Helvetica, Arial, sans-serif",
+        ("font", "serif") => "Georgia, Times New Roman, serif",
+        ("font", "monospace") => "Monaco, Courier New, monospace",
+        _ => value,
+    }
+}
+
+// the `a` format for a link, after the link resolver. None when demoted to plain text
+fn link_tag(href: &str, options: &RenderOptions) -> Option<FormatTag> {
+    let resolution = match &options.link_resolver {
+        Some(link_resolver) => link_resolver(href),
+        None => LinkResolution::Keep,
+    };
+    let (href, extra) = match resolution {
+        LinkResolution::Keep => (String::from(href), Vec::new()),
+        LinkResolution::Replace { href, attributes } => (href, attributes),
+        LinkResolution::Text => return None,
+    };
+
+    let policy = match &options.link_policy_for {
+        Some(link_policy_for) => link_policy_for(&href),
+        None => options.link_policy.clone(),
+    };
+
+    Some(FormatTag {
+        key: "a",
+        tag: "a",
+        context: Some(href),
+        link: Some(LinkAttributes { policy, extra }),
+    })
+}
+
+// wrap content in the same markup as the `link` format, used by linked embeds
+pub(crate) fn link(input: String, href: &str, options: &RenderOptions) -> String {
+    match link_tag(href, options) {
+        Some(link_tag) => {
+            let mut result = String::new();
+            // writing into a String never fails
+            link_tag.write_open(&mut result, "", "").unwrap();
+            result.push_str(&input);
+            link_tag.write_close(&mut result).unwrap();
+            result
+        }
+        None => input,
+    }
+}
+
+pub fn format(raw_input: String, attr: &Option<Value>) -> String {
+    format_with_options(raw_input, attr, &RenderOptions::default())
+}
+
+pub(crate) fn format_with_options(
+    raw_input: String,
+    attr: &Option<Value>,
+    options: &RenderOptions,
+) -> String {
+    format_runs(&[InlineRun::new(raw_input, attr, options)])
+}
+
+// a piece of a line with its marks, kept until the line ends so that adjacent runs
+// can share their outer marks
+pub(crate) struct InlineRun {
+    content: String,
+    // outermost first
+    marks: Vec<FormatTag>,
+    class_attrs_str: String,
+    styled_attrs_str: String,
+    // formatted text without marks is still wrapped in a span
+    span: bool,
+}
+
+impl InlineRun {
+    // rendered html, e.g. an embed
+    pub(crate) fn raw(content: String) -> InlineRun {
+        InlineRun {
+            content,
+            marks: Vec::new(),
+            class_attrs_str: String::new(),
+            styled_attrs_str: String::new(),
+            span: false,
+        }
+    }
+
+    pub(crate) fn new(
+        raw_input: String,
+        attr: &Option<Value>,
+        options: &RenderOptions,
+    ) -> InlineRun {
+        let mut run = InlineRun::raw(raw_input);
+        if let Some(Value::Object(inner_attr)) = attr {
+            let mut styled_attrs_str = String::from("");
+            let mut classes: Vec<String> = Vec::new();
+            let mut formatters: Vec<FormatTag> = Vec::new();
+            let mut link_demoted = false;
+            for (key, value) in inner_attr {
+                match key.as_str() {
+                    "link" => match link_tag(value.as_str().unwrap_or_default(), options) {
+                        Some(link_tag) => formatters.push(link_tag),
+                        None => link_demoted = true,
+                    },
+                    "underline" => {
+                        formatters.push(FormatTag {
+                            key: "underline",
+                            tag: "u",
+                            context: None,
+                            link: None,
+                        });
+                    }
+                    "strike" => {
+                        formatters.push(FormatTag {
+                            key: "strike",
+                            tag: "s",
+                            context: None,
+                            link: None,
+                        });
+                    }
+                    "italic" => {
+                        formatters.push(FormatTag {
+                            key: "italic",
+                            tag: "em",
+                            context: None,
+                            link: None,
+                        });
+                    }
+                    "bold" => {
+                        formatters.push(FormatTag {
+                            key: "bold",
+                            tag: "strong",
+                            context: None,
+                            link: None,
+                        });
+                    }
+                    "code" => {
+                        formatters.push(FormatTag {
+                            key: "code",
+                            tag: "code",
+                            context: None,
+                            link: None,
+                        });
+                    }
+                    "script" => {
+                        let tag = match value.as_str() {
+                            Some("super") => "sup",
+                            Some("sub") => "sub",
+                            _ => "",
+                        };
+                        if !tag.is_empty() {
+                            formatters.push(FormatTag {
+                                key: "script",
+                                tag,
+                                context: None,
+                                link: None,
+                            });
+                        }
+                    }
+                    "color" => {
+                        styled_attrs_str
+                            .push_str(&format!("color: {}; ", value.as_str().unwrap_or_default()));
+                    }
+                    "background" => {
+                        styled_attrs_str.push_str(&format!(
+                            "background-color: {}; ",
+                            value.as_str().unwrap_or_default()
+                        ));
+                    }
+                    "size" | "font" => {
+                        let value = value.as_str().unwrap_or_default();
+                        let (attributor, property) = if key == "size" {
+                            (options.size_attributor, "font-size")
+                        } else {
+                            (options.font_attributor, "font-family")
+                        };
+                        match attributor {
+                            // a value that is no class name is left out
+                            Attributor::Class if !options.inline_styles => {
+                                if is_class_name(value) {
+                                    classes
+                                        .push(format!("{}{}-{}", options.class_prefix, key, value));
+                                }
+                            }
+                            Attributor::Class => styled_attrs_str.push_str(&format!(
+                                "{}: {}; ",
+                                property,
+                                named_value(key, value)
+                            )),
+                            Attributor::Style => {
+                                styled_attrs_str.push_str(&format!("{}: {}; ", property, value))
+                            }
+                        }
+                    }
+                    _ => (),
                 }
-                _ => (),
             }
+            // outermost first, independent of the attribute order
+            formatters.sort_by_key(|item| MARK_ORDER.iter().position(|key| *key == item.key));
+
+            // a link demoted to plain text leaves no wrapper behind
+            run.span = !(formatters.is_empty()
+                && styled_attrs_str.is_empty()
+                && classes.is_empty()
+                && link_demoted);
+            run.marks = formatters;
+            run.class_attrs_str = classes.join(" ");
+            run.styled_attrs_str = styled_attrs_str;
         }
-        if formatters.len() == 0 {
-            formatters.push(FormatTag {
+        run
+    }
+
+    // the marks from `depth` on, the outermost of them carries the class and style
+    fn write_from<W: Write>(&self, out: &mut W, depth: usize) -> fmt::Result {
+        let marks = &self.marks[depth.min(self.marks.len())..];
+        if marks.is_empty() {
+            let wrapped = !self.class_attrs_str.is_empty()
+                || !self.styled_attrs_str.is_empty()
+                || (depth == 0 && self.span);
+            if !wrapped {
+                return out.write_str(&self.content);
+            }
+            let span = FormatTag {
                 key: "inline",
                 tag: "span",
                 context: None,
-            })
+                link: None,
+            };
+            span.write_open(out, &self.class_attrs_str, &self.styled_attrs_str)?;
+            out.write_str(&self.content)?;
+            return span.write_close(out);
         }
 
-        for (index, item) in formatters.iter().enumerate() {
-            // the last one
-            if index == formatters.len() - 1 && !styled_attrs_str.is_empty() {
-                raw_input = item.format(raw_input, Some(styled_attrs_str.clone()));
+        for (index, item) in marks.iter().enumerate() {
+            if index == 0 {
+                item.write_open(out, &self.class_attrs_str, &self.styled_attrs_str)?;
             } else {
-                raw_input = item.format(raw_input, None);
+                item.write_open(out, "", "")?;
+            }
+        }
+        out.write_str(&self.content)?;
+        for item in marks.iter().rev() {
+            item.write_close(out)?;
+        }
+        Ok(())
+    }
+}
+
+// adjacent runs sharing a mark are wrapped in a single element, e.g. one `<a>` for a
+// link split by a color change
+pub(crate) fn format_runs(runs: &[InlineRun]) -> String {
+    let mut result = String::new();
+    // writing into a String never fails
+    write_runs(&mut result, runs).unwrap();
+    result
+}
+
+pub(crate) fn write_runs<W: Write>(out: &mut W, runs: &[InlineRun]) -> fmt::Result {
+    write_runs_from(out, runs, 0)
+}
+
+fn write_runs_from<W: Write>(out: &mut W, runs: &[InlineRun], depth: usize) -> fmt::Result {
+    let mut index = 0;
+    while index < runs.len() {
+        let mark = runs[index].marks.get(depth);
+        let mut end = index + 1;
+        if mark.is_some() {
+            while end < runs.len() && runs[end].marks.get(depth) == mark {
+                end += 1;
+            }
+        }
+        match mark {
+            Some(mark) if end - index > 1 => {
+                mark.write_open(out, "", "")?;
+                write_runs_from(out, &runs[index..end], depth + 1)?;
+                mark.write_close(out)?;
             }
+            _ => runs[index].write_from(out, depth)?,
         }
+        index = end;
     }
-    raw_input
+    Ok(())
 }
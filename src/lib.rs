@@ -1,155 +1,155 @@
+use serde::de::DeserializeSeed;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::fmt::{self, Write};
+use std::io;
 
+pub mod asset;
+pub mod attachment;
 pub mod block_format;
+mod embed;
+pub mod html;
 pub mod inline_format;
+pub mod links;
+pub mod markdown;
+pub mod mention;
+pub mod normalize;
+pub mod options;
+mod renderer;
+pub mod resolve;
+pub mod schema;
+pub mod text;
+pub mod toc;
 
-use block_format::BlockState;
+pub use options::{Attributor, LinkPolicy, RenderOptions};
+pub use renderer::Renderer;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct DeltaOp {
     insert: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
     attributes: Option<Value>,
 }
 
 pub fn parser(delta_ops: Vec<DeltaOp>) -> String {
+    render(&delta_ops, &RenderOptions::default())
+}
+
+pub fn render(delta_ops: &[DeltaOp], options: &RenderOptions) -> String {
     let mut html = String::from("");
-    let mut reader = String::from("");
-    let mut block_state = BlockState::new();
+    // writing into a String never fails
+    render_fmt(delta_ops, options, &mut html).unwrap();
+    html
+}
 
-    for op in delta_ops.iter() {
-        if let Value::String(str_insert) = &op.insert {
-            let mut inner_reader = String::from("");
-            for (_, char) in str_insert.char_indices() {
-                if char != '\n' {
-                    // scan&store all content before a line break
-                    inner_reader.push(char);
-                } else {
-                    reader.push_str(&inner_reader);
-                    inner_reader.clear();
-                    let tmp_content = if reader.is_empty() {
-                        "<br>"
-                    } else {
-                        reader.as_str()
-                    };
-
-                    let mut pending = String::from("");
-                    if let Some(Value::Object(attr)) = &op.attributes {
-                        // need check if there has a intent attr
-                        if let Some(Value::String(list_type)) = attr.get("list") {
-                            pending = block_state.open_block(attr, list_type, &reader);
-                        } else if let Some(_) = attr.get("code-block") {
-                            pending =
-                                block_state.open_block(attr, &String::from("code-block"), &reader);
-                        } else if let Some(Value::Number(header)) = attr.get("header") {
-                            let result = if let Some(Value::String(align)) = attr.get("align") {
-                                format!(
-                                    "<h{} class=\"ql-align-{}\">{}</h{}>",
-                                    header, align, tmp_content, header
-                                )
-                            } else {
-                                format!("<h{}>{}</h{}>", header, tmp_content, header)
-                            };
-                            pending = format!(
-                                "{}{}",
-                                block_state.check_and_close_current_block(),
-                                &result
-                            );
-                        } else if let Some(Value::String(align)) = attr.get("align") {
-                            let result =
-                                format!("<p class=\"ql-align-{}\">{}</p>", align, tmp_content);
-                            pending = format!(
-                                "{}{}",
-                                block_state.check_and_close_current_block(),
-                                &result
-                            );
-                        }
-                    } else {
-                        let result = format!("<p>{}</p>", tmp_content);
-                        pending =
-                            format!("{}{}", block_state.check_and_close_current_block(), &result);
-                    }
-                    html.push_str(&pending);
-                    reader.clear();
-                }
-            }
-            // can not find a line break in this op
-            // try format the content with attr(if exist)
-            if !inner_reader.is_empty() {
-                reader.push_str(inline_format::format(inner_reader, &op.attributes).as_str());
-            }
-        } else if let Value::Object(obj_insert) = &op.insert {
-            if let Some(Value::String(savvy_image)) = obj_insert.get("savvy_image") {
-                let tmp_alt = match &op.attributes {
-                    Some(Value::Object(attr)) => {
-                        attr.get("alt").and_then(|v| v.as_str()).unwrap_or_default()
-                    }
-                    _ => "",
-                };
-
-                reader.push_str(&format!(
-                    "<img src=\"{}\" alt=\"{}\">",
-                    savvy_image, tmp_alt
-                ));
-            } else if let Some(Value::String(savvy_attach)) = obj_insert.get("savvy_attach") {
-                let tmp_alt = match &op.attributes {
-                    Some(Value::Object(attr)) => {
-                        attr.get("alt").and_then(|v| v.as_str()).unwrap_or_default()
-                    }
-                    _ => "",
-                };
+/// Render into an `io::Write`, e.g. a response body. The html is written in many small
+/// pieces, an unbuffered sink is best wrapped in a `BufWriter`.
+pub fn render_to<W: io::Write>(
+    delta_ops: &[DeltaOp],
+    options: &RenderOptions,
+    writer: W,
+) -> io::Result<()> {
+    let mut out = IoWriter {
+        inner: writer,
+        error: None,
+    };
+    render_fmt(delta_ops, options, &mut out).map_err(|_| {
+        out.error
+            .take()
+            .unwrap_or_else(|| io::Error::other("formatter error"))
+    })
+}
 
-                let format = savvy_attach.split(".").last().unwrap();
+// fmt::Write over an io::Write, keeping the io error fmt::Error can not carry
+struct IoWriter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
 
-                if format == "mp4" || format == "webm" || format == "ogg" {
-                    reader.push_str(&format!(
-                        "<video src=\"{}\" alt=\"{}\" controls>",
-                        savvy_attach, tmp_alt
-                    ));
-                } else {
-                    reader.push_str(&format!(
-                        "<img src=\"{}\" alt=\"{}\">",
-                        savvy_attach, tmp_alt
-                    ));
-                }
-            } else if let Some(Value::Object(mention)) = obj_insert.get("mention") {
-                let mention_index = mention
-                    .get("index")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or_default();
-                let mention_id = mention
-                    .get("id")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or_default();
-                let mention_value = mention
-                    .get("value")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or_default();
-
-                reader.push_str(&format!("<span class=\"mention\" data-index=\"{}\" data-denotation-char=\"@\" data-id=\"{}\" data-value=\"{}\">&#xFEFF;<span contenteditable=\"false\"><span class=\"ql-mention-denotation-char\">@</span>{}</span>&#xFEFF;</span>", mention_index, mention_id, mention_value, mention_value));
-            } else if let Some(Value::String(video)) = obj_insert.get("video") {
-                // "<iframe class=\"ql-video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"https://media.w3.org/2010/05/sintel/trailer.mp4\"></iframe>"
-                reader.push_str(&format!(
-                    "<iframe class=\"ql-video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"{}\"></iframe>",
-                    video
-                ));
-            }
-        }
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
     }
+}
 
-    if !reader.is_empty() {
-        html.push_str(&format!("<p>{}</p>", reader));
-        reader.clear();
+/// Render into a `fmt::Write`, each line is written as soon as it ends.
+pub fn render_fmt<W: Write>(
+    delta_ops: &[DeltaOp],
+    options: &RenderOptions,
+    out: &mut W,
+) -> fmt::Result {
+    let mut renderer = Renderer::new(options);
+    for op in delta_ops.iter() {
+        renderer.push(op, out)?;
     }
-    html.push_str(&block_state.check_and_close_current_block());
+    renderer.finish(out)
+}
 
-    html
+/// Render the ops of an iterator, holding one op at a time.
+pub fn render_iter<I: IntoIterator<Item = DeltaOp>, W: Write>(
+    delta_ops: I,
+    options: &RenderOptions,
+    out: &mut W,
+) -> fmt::Result {
+    let mut renderer = Renderer::new(options);
+    for op in delta_ops {
+        renderer.push(&op, out)?;
+    }
+    renderer.finish(out)
+}
+
+/// Render a json array of ops, or a `{"ops": [...]}` delta, deserializing one op at a
+/// time. The reader is read byte by byte, an unbuffered one is best wrapped in a
+/// `BufReader`.
+pub fn render_json<R: io::Read, W: io::Write>(
+    reader: R,
+    options: &RenderOptions,
+    writer: W,
+) -> io::Result<()> {
+    let mut out = IoWriter {
+        inner: writer,
+        error: None,
+    };
+    let mut renderer = Renderer::new(options);
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = renderer::OpsSeed {
+        renderer: &mut renderer,
+        out: &mut out,
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|_| deserializer.end());
+    if let Err(error) = result {
+        // a failed write is reported as a deserialization error
+        return Err(out.error.take().unwrap_or_else(|| error.into()));
+    }
+    renderer.finish(&mut out).map_err(|_| {
+        out.error
+            .take()
+            .unwrap_or_else(|| io::Error::other("formatter error"))
+    })
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::asset::{assets, Asset, AssetKind};
+    use crate::attachment::{AttachmentKind, AttachmentTable};
+    use crate::block_format::BlockState;
+    use crate::html::{html_to_delta, html_to_delta_with_options};
+    use crate::links::{links, Link};
+    use crate::markdown::markdown_to_delta;
+    use crate::normalize::normalize;
     use crate::parser;
-    use crate::DeltaOp;
+    use crate::resolve::{LinkResolution, MentionResolution};
+    use crate::schema::{Schema, ValueType, Violation, ViolationKind};
+    use crate::text::text_to_delta;
+    use crate::toc::{render_toc, toc};
+    use crate::{
+        render, render_fmt, render_iter, render_json, render_to, Attributor, DeltaOp, LinkPolicy,
+        RenderOptions, Renderer,
+    };
     use serde_json::json;
     use serde_json::Value;
 
@@ -176,7 +176,7 @@ mod tests {
                 attributes: None,
             },
         ]);
-        assert_eq!(result, String::from("<p><u><s><a href=\"https://www.test.com\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"https://www.test.com\"><em><strong>hello world</strong></em></a></s></u></p>"));
+        assert_eq!(result, String::from("<p><a href=\"https://www.test.com\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"https://www.test.com\"><strong><em><s><u>hello world</u></s></em></strong></a></p>"));
     }
 
     #[test]
@@ -244,7 +244,7 @@ mod tests {
             },
         ]);
 
-        assert_eq!(result, String::from("<ol><li>1<strong>23</strong></li><li class=\"ql-align-center\">center</li><li class=\"ql-align-right\">right</li><li><u><s><a href=\"abc\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"abc\"><em>abc</em></a></s></u><a href=\"abc\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"abc\">➗</a></li></ol>"));
+        assert_eq!(result, String::from("<ol><li>1<strong>23</strong></li><li class=\"ql-align-center\">center</li><li class=\"ql-align-right\">right</li><li><a href=\"abc\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"abc\"><em><s><u>abc</u></s></em>➗</a></li></ol>"));
     }
 
     #[test]
@@ -380,7 +380,7 @@ mod tests {
                 attributes: None,
             },
         ]);
-        assert_eq!(result, String::from("<p>asd</p><p><img src=\"path/to/image.webp\" alt=\"WeChat Image_20210616141455.png\"><video src=\"path/to/video.mp4\" alt=\"WeChat Image_20210616141455.mp4\" controls>sss</p>"));
+        assert_eq!(result, String::from("<p>asd</p><p><img src=\"path/to/image.webp\" alt=\"WeChat Image_20210616141455.png\"><video src=\"path/to/video.mp4\" alt=\"WeChat Image_20210616141455.mp4\" controls></video>sss</p>"));
     }
 
     #[test]
@@ -426,7 +426,7 @@ mod tests {
                 attributes: Some(json!({"list": "ordered"})),
             },
         ]);
-        assert_eq!(result, String::from("<pre class=\"ql-syntax\" spellcheck=\"false\">package newproject;\nimport org.openqa.selenium.By;\n</pre><ol><li><em><strong>aaa</strong></em></li></ol>"));
+        assert_eq!(result, String::from("<pre class=\"ql-syntax\" spellcheck=\"false\">package newproject;\nimport org.openqa.selenium.By;\n</pre><ol><li><strong><em>aaa</em></strong></li></ol>"));
     }
 
     #[test]
@@ -524,4 +524,1178 @@ mod tests {
 
         assert_eq!(result, String::from("<p><iframe class=\"ql-video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"https://media.w3.org/2010/05/sintel/trailer.mp4\"></iframe></p>"));
     }
+
+    #[test]
+    fn test_toc() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("Getting Started")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 1})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("intro\nInstall")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 2})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("Install")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 2, "align": "center"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("Usage")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 1})),
+            },
+        ];
+
+        let entries = toc(&delta_ops);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].children.len(), 2);
+        assert_eq!(entries[0].children[0].id, "install");
+        assert_eq!(entries[0].children[0].op_index, 2);
+        assert_eq!(entries[0].children[0].offset, 22);
+        assert_eq!(entries[0].children[1].id, "install-1");
+        assert_eq!(entries[1].text, "Usage");
+        assert_eq!(render_toc(&entries), String::from("<ul><li><a href=\"#getting-started\">Getting Started</a><ul><li><a href=\"#install\">Install</a></li><li><a href=\"#install-1\">Install</a></li></ul></li><li><a href=\"#usage\">Usage</a></li></ul>"));
+
+        let result = render(&delta_ops, &RenderOptions::new().header_ids(true));
+        assert_eq!(result, String::from("<h1 id=\"getting-started\">Getting Started</h1><p>intro</p><h2 id=\"install\">Install</h2><h2 id=\"install-1\" class=\"ql-align-center\">Install</h2><h1 id=\"usage\">Usage</h1>"));
+
+        // a suffixed id taken by another header is skipped
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "Intro"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "Intro 1"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "Intro"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "Intro 1"},
+            {"insert": "\n", "attributes": {"header": 1}}
+        ]))
+        .unwrap();
+        let ids: Vec<String> = toc(&delta_ops).into_iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec!["intro", "intro-1", "intro-2", "intro-1-1"]);
+
+        // a header level that is not an integer is no header, for the toc and the html
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "A"},
+            {"insert": "\n", "attributes": {"header": 2.0}},
+            {"insert": "A"},
+            {"insert": "\n", "attributes": {"header": 2}}
+        ]))
+        .unwrap();
+        assert_eq!(toc(&delta_ops)[0].id, "a");
+        let result = render(&delta_ops, &RenderOptions::new().header_ids(true));
+        assert_eq!(result, String::from("<p>A</p><h2 id=\"a\">A</h2>"));
+
+        // the header text is escaped in the toc
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "A <b>"},
+            {"insert": "\n", "attributes": {"header": 1}}
+        ]))
+        .unwrap();
+        assert_eq!(
+            render_toc(&toc(&delta_ops)),
+            String::from("<ul><li><a href=\"#a-b\">A &lt;b&gt;</a></li></ul>")
+        );
+    }
+
+    #[test]
+    fn test_direction() {
+        let result = parser(vec![
+            DeltaOp {
+                insert: Value::String(String::from("مرحبا")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"direction": "rtl"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("שלום")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 2, "align": "right", "direction": "rtl"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("aaa")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"list": "bullet", "direction": "rtl"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("bbb")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(
+                    json!({"list": "bullet", "indent": 1, "align": "right", "direction": "rtl"}),
+                ),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("let a = 1;")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"code-block": true, "direction": "rtl"})),
+            },
+        ]);
+        assert_eq!(result, String::from("<p class=\"ql-direction-rtl\">مرحبا</p><h2 class=\"ql-align-right ql-direction-rtl\">שלום</h2><ul><li class=\"ql-direction-rtl\">aaa</li><ul><li class=\"ql-indent-1 ql-align-right ql-direction-rtl\">bbb</li></ul></ul><pre class=\"ql-syntax ql-direction-rtl\" spellcheck=\"false\">let a = 1;\n</pre>"));
+    }
+
+    #[test]
+    fn test_paragraph_indent() {
+        let result = parser(vec![
+            DeltaOp {
+                insert: Value::String(String::from("Title")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 2, "indent": 1})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("quoted text")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"indent": 2, "align": "center"})),
+            },
+        ]);
+        assert_eq!(result, String::from("<h2 class=\"ql-indent-1\">Title</h2><p class=\"ql-indent-2 ql-align-center\">quoted text</p>"));
+    }
+
+    #[test]
+    fn test_script() {
+        let result = parser(vec![
+            DeltaOp {
+                insert: Value::String(String::from("H")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("2")),
+                attributes: Some(json!({"script": "sub"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("O, see")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("1")),
+                attributes: Some(json!({"script": "super", "bold": true, "link": "#note-1"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ]);
+        assert_eq!(result, String::from("<p>H<sub>2</sub>O, see<a href=\"#note-1\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"#note-1\"><sup><strong>1</strong></sup></a></p>"));
+    }
+
+    #[test]
+    fn test_formula() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({"formula": "e^{i\\pi} < 0"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = render(&delta_ops, &RenderOptions::default());
+        assert_eq!(
+            result,
+            String::from(
+                "<p><span class=\"ql-formula\" data-value=\"e^{i\\pi} &lt; 0\"></span></p>"
+            )
+        );
+
+        let options =
+            RenderOptions::new().formula_renderer(|tex| format!("<math>{}</math>", tex.len()));
+        let result = render(&delta_ops, &options);
+        assert_eq!(
+            result,
+            String::from("<p><span class=\"ql-formula\" data-value=\"e^{i\\pi} &lt; 0\"><math>12</math></span></p>")
+        );
+    }
+
+    #[test]
+    fn test_core_image() {
+        let result = parser(vec![
+            DeltaOp {
+                insert: json!({"image": "https://cdn.test.com/a.png"}),
+                attributes: Some(json!({"width": "300", "height": 200, "alt": "a \"quoted\" alt"})),
+            },
+            DeltaOp {
+                insert: json!({"image": "https://cdn.test.com/b.png"}),
+                attributes: Some(
+                    json!({"style": "display: block; margin: auto;", "link": "https://www.test.com"}),
+                ),
+            },
+            DeltaOp {
+                insert: json!({"savvy_image": "path/to/image"}),
+                attributes: Some(json!({"width": "50%"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ]);
+        // core images carry the image class, savvy images never do
+        assert_eq!(result, String::from("<p><img class=\"ql-image\" src=\"https://cdn.test.com/a.png\" alt=\"a &quot;quoted&quot; alt\" width=\"300\" height=\"200\"><a href=\"https://www.test.com\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"https://www.test.com\"><img class=\"ql-image\" src=\"https://cdn.test.com/b.png\" style=\"display: block; margin: auto;\"></a><img src=\"path/to/image\" alt=\"\" width=\"50%\"></p>"));
+    }
+
+    #[test]
+    fn test_attach_kinds() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({"savvy_attach": "path/to/Song.MP3?token=abc"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: json!({"savvy_attach": "path/to/report.pdf"}),
+                attributes: Some(json!({"size": 1572864})),
+            },
+            DeltaOp {
+                insert: json!({"savvy_attach": "path/to/blob"}),
+                attributes: Some(json!({"type": "image/png", "alt": "blob"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = parser(delta_ops);
+        assert_eq!(result, String::from("<p><audio src=\"path/to/Song.MP3?token=abc\" controls></audio><a href=\"path/to/report.pdf\" download=\"report.pdf\">report.pdf (1.5 MB)</a><img src=\"path/to/blob\" alt=\"blob\"></p>"));
+
+        // the name and the size are escaped in the label
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({"savvy_attach": "path/to/a.pdf"}),
+                attributes: Some(json!({"name": "<b>a&b</b>.pdf", "size": "<1 KB>"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = parser(delta_ops);
+        assert_eq!(result, String::from("<p><a href=\"path/to/a.pdf\" download=\"&lt;b&gt;a&amp;b&lt;/b&gt;.pdf\">&lt;b&gt;a&amp;b&lt;/b&gt;.pdf (&lt;1 KB&gt;)</a></p>"));
+
+        let table = AttachmentTable::new().extension("pdf", AttachmentKind::Image);
+        assert_eq!(
+            table.classify("path/to/a.PDF#page=2", None),
+            AttachmentKind::Image
+        );
+        assert_eq!(
+            table.classify("path/to/a.mp4", None),
+            AttachmentKind::Download
+        );
+    }
+
+    #[test]
+    fn test_mention_options() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({
+                    "mention": {
+                        "denotationChar": "#",
+                        "id": "7",
+                        "index": "0",
+                        "value": "release",
+                        "link": "/tags/7"
+                    }
+                }),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = render(&delta_ops, &RenderOptions::default());
+        assert_eq!(result, String::from("<p><span class=\"mention\" data-index=\"0\" data-denotation-char=\"#\" data-id=\"7\" data-value=\"release\" data-link=\"/tags/7\">&#xFEFF;<span contenteditable=\"false\"><span class=\"ql-mention-denotation-char\">#</span>release</span>&#xFEFF;</span></p>"));
+
+        let result = render(
+            &delta_ops,
+            &RenderOptions::new().mention_href("/users/{id}"),
+        );
+        assert_eq!(result, String::from("<p><a href=\"/users/7\" class=\"mention\" data-index=\"0\" data-denotation-char=\"#\" data-id=\"7\" data-value=\"release\" data-link=\"/tags/7\">#release</a></p>"));
+
+        // the denotation char and the value are text
+        let text_ops = vec![
+            DeltaOp {
+                insert: json!({"mention": {"denotationChar": "<", "id": "1", "value": "<img src=x onerror=alert(1)>"}}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = render(&text_ops, &RenderOptions::default());
+        assert_eq!(result, String::from("<p><span class=\"mention\" data-index=\"\" data-denotation-char=\"&lt;\" data-id=\"1\" data-value=\"&lt;img src=x onerror=alert(1)&gt;\">&#xFEFF;<span contenteditable=\"false\"><span class=\"ql-mention-denotation-char\">&lt;</span>&lt;img src=x onerror=alert(1)&gt;</span>&#xFEFF;</span></p>"));
+        let result = render(&text_ops, &RenderOptions::new().mention_href("/users/{id}"));
+        assert_eq!(result, String::from("<p><a href=\"/users/1\" class=\"mention\" data-index=\"\" data-denotation-char=\"&lt;\" data-id=\"1\" data-value=\"&lt;img src=x onerror=alert(1)&gt;\">&lt;&lt;img src=x onerror=alert(1)&gt;</a></p>"));
+
+        // field names that are not attribute names are left out
+        let unnamed_ops = vec![
+            DeltaOp {
+                insert: json!({
+                    "mention": {
+                        "id": "7",
+                        "value": "x",
+                        "a\"><script>alert(1)</script": "y",
+                        "on click": "z",
+                        "user_id": "8"
+                    }
+                }),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = render(&unnamed_ops, &RenderOptions::default());
+        assert_eq!(result, String::from("<p><span class=\"mention\" data-index=\"\" data-denotation-char=\"@\" data-id=\"7\" data-value=\"x\" data-user-id=\"8\">&#xFEFF;<span contenteditable=\"false\"><span class=\"ql-mention-denotation-char\">@</span>x</span>&#xFEFF;</span></p>"));
+
+        let options = RenderOptions::new().mention_renderer(|mention| {
+            format!("<b>{}</b>", mention["value"].as_str().unwrap_or_default())
+        });
+        assert_eq!(
+            render(&delta_ops, &options),
+            String::from("<p><b>release</b></p>")
+        );
+    }
+
+    #[test]
+    fn test_resolvers() {
+        let mention = |id: &str, value: &str| DeltaOp {
+            insert: json!({"mention": {"denotationChar": "@", "id": id, "index": "0", "value": value}}),
+            attributes: None,
+        };
+        let delta_ops = vec![
+            mention("1", "old name"),
+            mention("2", "deleted"),
+            mention("3", "bot"),
+            DeltaOp {
+                insert: Value::String(String::from("docs")),
+                attributes: Some(json!({"link": "/d/1?ref=x"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from(" spam")),
+                attributes: Some(json!({"link": "https://spam.test"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let options = RenderOptions::new()
+            .mention_resolver(|mention| match mention["id"].as_str() {
+                Some("1") => {
+                    let mut mention = mention.clone();
+                    mention.insert(String::from("value"), json!("New Name"));
+                    MentionResolution::Replace(mention)
+                }
+                Some("2") => MentionResolution::Remove,
+                _ => MentionResolution::Text,
+            })
+            .link_resolver(|href| {
+                if href.starts_with("/d/") {
+                    LinkResolution::Replace {
+                        href: String::from("https://docs.test/1"),
+                        attributes: vec![(String::from("data-internal"), String::from("true"))],
+                    }
+                } else {
+                    LinkResolution::Text
+                }
+            });
+        let result = render(&delta_ops, &options);
+        assert_eq!(result, String::from("<p><span class=\"mention\" data-index=\"0\" data-denotation-char=\"@\" data-id=\"1\" data-value=\"New Name\">&#xFEFF;<span contenteditable=\"false\"><span class=\"ql-mention-denotation-char\">@</span>New Name</span>&#xFEFF;</span>@bot<a href=\"https://docs.test/1\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"https://docs.test/1\" data-internal=\"true\">docs</a> spam</p>"));
+
+        // mentions demoted to plain text stay text
+        let text_ops = vec![
+            mention("4", "<b>x</b>"),
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        assert_eq!(
+            render(&text_ops, &options),
+            String::from("<p>@&lt;b&gt;x&lt;/b&gt;</p>")
+        );
+    }
+
+    #[test]
+    fn test_url_rewriter() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({"savvy_image": "uploads/a.png"}),
+                attributes: Some(json!({"alt": "a"})),
+            },
+            DeltaOp {
+                insert: json!({"savvy_attach": "uploads/b.mp4"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: json!({"video": "uploads/c.mp4"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: json!({"image": "uploads/missing.png"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let options = RenderOptions::new().url_rewriter(|kind, url, _| {
+            if url.contains("missing") {
+                return None;
+            }
+            match kind {
+                AssetKind::Video => Some(format!("https://video.test/{}", url)),
+                _ => Some(format!("https://cdn.test/{}?sig=1&t=2", url)),
+            }
+        });
+        let result = render(&delta_ops, &options);
+        assert_eq!(result, String::from("<p><img src=\"https://cdn.test/uploads/a.png?sig=1&amp;t=2\" alt=\"a\"><video src=\"https://cdn.test/uploads/b.mp4?sig=1&amp;t=2\" alt=\"\" controls></video><iframe class=\"ql-video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"https://video.test/uploads/c.mp4\"></iframe></p>"));
+
+        // video urls are escaped like the others
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({"video": "https://video.test/a.mp4?t=1&q=\"><script>"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let result = render(&delta_ops, &RenderOptions::default());
+        assert_eq!(result, String::from("<p><iframe class=\"ql-video\" frameborder=\"0\" allowfullscreen=\"true\" src=\"https://video.test/a.mp4?t=1&amp;q=&quot;&gt;&lt;script&gt;\"></iframe></p>"));
+    }
+
+    #[test]
+    fn test_assets() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: json!({"savvy_image": "uploads/a.png"}),
+                attributes: Some(json!({"alt": "a", "link": "https://www.test.com"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("see ")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("docs")),
+                attributes: Some(json!({"link": "https://docs.test"})),
+            },
+            DeltaOp {
+                insert: json!({"savvy_attach": "uploads/b.pdf"}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: json!({"mention": {"id": "1", "value": "Alan"}}),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        assert_eq!(
+            assets(&delta_ops),
+            vec![
+                Asset {
+                    op_index: 0,
+                    kind: AssetKind::SavvyImage,
+                    url: String::from("uploads/a.png"),
+                    alt: Some(String::from("a")),
+                },
+                Asset {
+                    op_index: 0,
+                    kind: AssetKind::Link,
+                    url: String::from("https://www.test.com"),
+                    alt: Some(String::from("a")),
+                },
+                Asset {
+                    op_index: 2,
+                    kind: AssetKind::Link,
+                    url: String::from("https://docs.test"),
+                    alt: None,
+                },
+                Asset {
+                    op_index: 3,
+                    kind: AssetKind::SavvyAttach,
+                    url: String::from("uploads/b.pdf"),
+                    alt: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("read ")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("the ")),
+                attributes: Some(json!({"link": "https://docs.test/"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("docs")),
+                attributes: Some(json!({"link": "https://docs.test/", "bold": true})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\nwww.test.com")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("www.spam.test")),
+                attributes: Some(json!({"link": "https://www.spam.test"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        assert_eq!(
+            links(&delta_ops),
+            vec![
+                Link {
+                    href: String::from("https://docs.test/"),
+                    text: String::from("the docs"),
+                    op_index: 1,
+                    offset: 5,
+                    text_is_url: false,
+                },
+                Link {
+                    href: String::from("https://www.spam.test"),
+                    text: String::from("www.spam.test"),
+                    op_index: 4,
+                    offset: 26,
+                    text_is_url: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_link_policy() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("home")),
+                attributes: Some(json!({"link": "/home"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("other")),
+                attributes: Some(json!({"link": "https://other.test", "color": "red"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let options = RenderOptions::new()
+            .link_policy(LinkPolicy::default().rel(Some("nofollow ugc")).title(false));
+        assert_eq!(render(&delta_ops, &options), String::from("<p><a href=\"/home\" rel=\"nofollow ugc\" target=\"_blank\">home</a><a href=\"https://other.test\" rel=\"nofollow ugc\" target=\"_blank\" style=\"color: red; \">other</a></p>"));
+
+        let options = RenderOptions::new().link_policy_for(|href| {
+            if href.starts_with('/') {
+                LinkPolicy::same_tab()
+            } else {
+                LinkPolicy::default().title(false)
+            }
+        });
+        assert_eq!(render(&delta_ops, &options), String::from("<p><a href=\"/home\">home</a><a href=\"https://other.test\" rel=\"noopener noreferrer\" target=\"_blank\" style=\"color: red; \">other</a></p>"));
+
+        // the href and the title repeating it are escaped
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("x")),
+                attributes: Some(json!({"link": "/a?b=1&c=\" onmouseover=\"alert(1)"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        assert_eq!(parser(delta_ops), String::from("<p><a href=\"/a?b=1&amp;c=&quot; onmouseover=&quot;alert(1)\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"/a?b=1&amp;c=&quot; onmouseover=&quot;alert(1)\">x</a></p>"));
+    }
+
+    #[test]
+    fn test_render_options() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("hello")),
+                attributes: Some(json!({"link": "/home"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"align": "center"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("fn main() {}")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"code-block": true})),
+            },
+        ];
+        // the default options render what parser always did
+        assert_eq!(render(&delta_ops, &RenderOptions::default()), String::from("<p class=\"ql-align-center\"><a href=\"/home\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"/home\">hello</a></p><p><br></p><pre class=\"ql-syntax\" spellcheck=\"false\">fn main() {}\n</pre>"));
+
+        // open_block keeps its signature, with the default options
+        let mut block_state = BlockState::new();
+        let attr = json!({"list": "bullet", "align": "center"});
+        assert_eq!(
+            block_state.open_block(attr.as_object().unwrap(), "bullet", &String::from("a")),
+            String::from("<ul><li class=\"ql-align-center\">a</li>")
+        );
+        let options = RenderOptions::new().class_prefix("editor-");
+        assert_eq!(
+            block_state.open_block_with_options(attr.as_object().unwrap(), "bullet", "b", &options),
+            String::from("<li class=\"editor-align-center\">b</li>")
+        );
+        assert_eq!(block_state.check_and_close_current_block(), "</ul>");
+
+        let options = RenderOptions::new()
+            .paragraph_tag("div")
+            .empty_line("&nbsp;")
+            .class_prefix("editor-")
+            .code_block_class("code")
+            .link_target(None);
+        assert_eq!(render(&delta_ops, &options), String::from("<div class=\"editor-align-center\"><a href=\"/home\" rel=\"noopener noreferrer\" title=\"/home\">hello</a></div><div>&nbsp;</div><pre class=\"code\" spellcheck=\"false\">fn main() {}\n</pre>"));
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("title")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"header": 1, "align": "center"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("text")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"indent": 2})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("aaa")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"list": "bullet"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("bbb")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: Some(json!({"list": "bullet", "indent": 1, "direction": "rtl"})),
+            },
+        ];
+        let result = render(&delta_ops, &RenderOptions::new().inline_styles(true));
+        assert_eq!(result, String::from("<h1 style=\"text-align: center; \">title</h1><p style=\"padding-left: 6em; \">text</p><ul><li>aaa</li><ul><li style=\"padding-right: 4.5em; direction: rtl; text-align: inherit; \">bbb</li></ul></ul>"));
+    }
+
+    #[test]
+    fn test_class_attributors() {
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("big")),
+                attributes: Some(json!({"size": "large", "font": "serif"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("code")),
+                attributes: Some(json!({"font": "monospace", "bold": true})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let options = RenderOptions::new()
+            .size_attributor(Attributor::Class)
+            .font_attributor(Attributor::Class);
+        assert_eq!(render(&delta_ops, &options), String::from("<p><span class=\"ql-font-serif ql-size-large\">big</span><strong class=\"ql-font-monospace\">code</strong></p>"));
+
+        let options = RenderOptions::new()
+            .size_attributor(Attributor::Class)
+            .inline_styles(true);
+        assert_eq!(render(&delta_ops, &options), String::from("<p><span style=\"font-family: serif; font-size: 1.5em; \">big</span><strong style=\"font-family: monospace; \">code</strong></p>"));
+
+        // only class names become classes
+        let delta_ops = vec![
+            DeltaOp {
+                insert: Value::String(String::from("x")),
+                attributes: Some(
+                    json!({"size": "\" onclick=\"x", "font": "sans-serif", "bold": true}),
+                ),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ];
+        let options = RenderOptions::new()
+            .size_attributor(Attributor::Class)
+            .font_attributor(Attributor::Class);
+        assert_eq!(
+            render(&delta_ops, &options),
+            String::from("<p><strong class=\"ql-font-sans-serif\">x</strong></p>")
+        );
+    }
+
+    #[test]
+    fn test_merge_runs() {
+        let result = parser(vec![
+            DeltaOp {
+                insert: Value::String(String::from("bold ")),
+                attributes: Some(json!({"link": "https://www.test.com", "bold": true})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("red")),
+                attributes: Some(
+                    json!({"link": "https://www.test.com", "bold": true, "color": "red"}),
+                ),
+            },
+            DeltaOp {
+                insert: Value::String(String::from(" link")),
+                attributes: Some(json!({"link": "https://www.test.com", "bold": true})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from(" plain ")),
+                attributes: None,
+            },
+            DeltaOp {
+                insert: Value::String(String::from("a")),
+                attributes: Some(json!({"color": "red"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("b")),
+                attributes: Some(json!({"color": "blue"})),
+            },
+            DeltaOp {
+                insert: Value::String(String::from("\n")),
+                attributes: None,
+            },
+        ]);
+        assert_eq!(result, String::from("<p><a href=\"https://www.test.com\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"https://www.test.com\"><strong>bold <span style=\"color: red; \">red</span> link</strong></a> plain <span style=\"color: red; \">a</span><span style=\"color: blue; \">b</span></p>"));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": ""},
+            {"insert": "hello ", "attributes": {"bold": null, "italic": false}},
+            {"insert": "world", "attributes": {}},
+            {"insert": "\n", "attributes": {"header": 1, "bold": true}},
+            {"insert": "bold", "attributes": {"bold": true, "align": "center"}},
+            {"insert": "\nitem", "attributes": {"list": "bullet", "indent": 0}},
+            {"insert": {"savvy_image": "path/to/image"}, "attributes": {"alt": "a", "list": "bullet"}}
+        ]))
+        .unwrap();
+        let expected: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "hello world"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "bold", "attributes": {"bold": true}},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "item"},
+            {"insert": {"savvy_image": "path/to/image"}, "attributes": {"alt": "a"}},
+            {"insert": "\n"}
+        ]))
+        .unwrap();
+        assert_eq!(normalize(&delta_ops), expected);
+        assert_eq!(normalize(&expected), expected);
+        assert_eq!(
+            serde_json::to_string(&normalize(&expected)[0]).unwrap(),
+            "{\"insert\":\"hello world\"}"
+        );
+    }
+
+    #[test]
+    fn test_schema() {
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "hello", "attributes": {"bold": true, "italic": false, "color": "#242729", "link": "/docs"}},
+            {"insert": "x", "attributes": {"color": "rgba(0, 0, 0, 50%)", "background": "var(--black-075)"}},
+            {"insert": "\n", "attributes": {"header": 2, "indent": 8, "code-block": false}},
+            {"insert": {"formula": "e=mc^2"}},
+            {"insert": "\n"}
+        ]))
+        .unwrap();
+        assert_eq!(Schema::default().validate(&delta_ops), Ok(()));
+
+        // only numbers inside color functions, only a name inside var()
+        for color in [
+            "rgb(\" onmouseover=\"alert(1))",
+            "rgb(1;2)",
+            "hsl((1))",
+            "var(--a) x(",
+            "var(--)",
+        ]
+        .iter()
+        {
+            let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+                {"insert": "a", "attributes": {"color": color}},
+                {"insert": "\n"}
+            ]))
+            .unwrap();
+            assert_eq!(
+                Schema::default().validate(&delta_ops),
+                Err(vec![Violation {
+                    op_index: 0,
+                    kind: ViolationKind::InvalidValue {
+                        attribute: String::from("color"),
+                        value: json!(color),
+                    },
+                }])
+            );
+        }
+
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "hello", "attributes": {"color": "red;x", "link": "javascript:alert(1)", "blink": true}},
+            {"insert": "\n", "attributes": {"header": 7}},
+            {"insert": {"tweet": "1"}}
+        ]))
+        .unwrap();
+        assert_eq!(
+            Schema::default().max_length(6).validate(&delta_ops),
+            Err(vec![
+                Violation {
+                    op_index: 0,
+                    kind: ViolationKind::UnknownAttribute(String::from("blink")),
+                },
+                Violation {
+                    op_index: 0,
+                    kind: ViolationKind::InvalidValue {
+                        attribute: String::from("color"),
+                        value: json!("red;x"),
+                    },
+                },
+                Violation {
+                    op_index: 0,
+                    kind: ViolationKind::InvalidValue {
+                        attribute: String::from("link"),
+                        value: json!("javascript:alert(1)"),
+                    },
+                },
+                Violation {
+                    op_index: 1,
+                    kind: ViolationKind::InvalidValue {
+                        attribute: String::from("header"),
+                        value: json!(7),
+                    },
+                },
+                Violation {
+                    op_index: 2,
+                    kind: ViolationKind::UnknownEmbed(String::from("tweet")),
+                },
+                Violation {
+                    op_index: 2,
+                    kind: ViolationKind::TooLong {
+                        length: 7,
+                        max_length: 6,
+                    },
+                },
+                Violation {
+                    op_index: 2,
+                    kind: ViolationKind::MissingTrailingNewline,
+                },
+            ])
+        );
+
+        let schema = Schema::new()
+            .attribute("bold", ValueType::True)
+            .embed("image");
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "a", "attributes": {"italic": true}},
+            {"insert": {"image": "a.png"}}
+        ]))
+        .unwrap();
+        assert_eq!(
+            schema.validate(&delta_ops),
+            Err(vec![Violation {
+                op_index: 0,
+                kind: ViolationKind::UnknownAttribute(String::from("italic")),
+            }])
+        );
+    }
+
+    #[test]
+    fn test_html_to_delta() {
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "Title"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "plain "},
+            {"insert": "bold link", "attributes": {"bold": true, "link": "https://www.test.com"}},
+            {"insert": " & ", "attributes": {"color": "#e60000"}},
+            {"insert": "2", "attributes": {"script": "super"}},
+            {"insert": {"mention": {"denotationChar": "@", "id": "1", "index": "0", "value": "alan"}}},
+            {"insert": {"formula": "e=mc^2"}},
+            {"insert": "\n", "attributes": {"align": "center", "indent": 2}},
+            {"insert": "\none"},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "two", "attributes": {"italic": true}},
+            {"insert": "\n", "attributes": {"list": "bullet", "indent": 1}},
+            {"insert": "fn main() {}"},
+            {"insert": "\n\n", "attributes": {"code-block": true}},
+            {"insert": {"savvy_image": "path/to/image"}, "attributes": {"alt": "a <b>"}},
+            {"insert": {"image": "path/to/b.png"}},
+            {"insert": {"image": "path/to/c.png"}, "attributes": {"alt": "c", "width": "120"}},
+            {"insert": {"video": "https://www.test.com/video"}},
+            {"insert": "\n"}
+        ]))
+        .unwrap();
+        let html = parser(delta_ops.clone());
+        assert_eq!(html_to_delta(&html), normalize(&delta_ops));
+
+        // classes are read with the prefix they were rendered with
+        let options = RenderOptions::new().class_prefix("x-");
+        let html = render(&delta_ops, &options);
+        assert_eq!(
+            html_to_delta_with_options(&html, &options),
+            normalize(&delta_ops)
+        );
+
+        let html = "<!DOCTYPE html>\n<div>\n  <p>a&nbsp;<b>b</b>\n  <i>c</i></p>\n  <ul>\n    <li>x<ul><li>y</li></ul></li>\n    <li>z\n  </ul>\n<pre><code>let a = 1;</code></pre><p>1 &lt; 2<br>3</div>";
+        let expected: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "a\u{a0}"},
+            {"insert": "b", "attributes": {"bold": true}},
+            {"insert": " "},
+            {"insert": "c", "attributes": {"italic": true}},
+            {"insert": "\nx"},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "y"},
+            {"insert": "\n", "attributes": {"list": "bullet", "indent": 1}},
+            {"insert": "z"},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "let a = 1;"},
+            {"insert": "\n", "attributes": {"code-block": true}},
+            {"insert": "1 < 2\n3\n"}
+        ]))
+        .unwrap();
+        assert_eq!(html_to_delta(html), expected);
+
+        // text nodes starting with a multi-byte character
+        let expected: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "éa\n"},
+            {"insert": "粗", "attributes": {"bold": true}},
+            {"insert": "体\n"}
+        ]))
+        .unwrap();
+        assert_eq!(html_to_delta("<p>éa</p><p><b>粗</b>体</p>"), expected);
+
+        // a list of another type nested in an item
+        let delta_ops = html_to_delta("<ul><li>a<ol><li>b</li></ol></li><li>c</li></ul>");
+        assert_eq!(
+            parser(delta_ops),
+            "<ul><li>a</li><ol><li class=\"ql-indent-1\">b</li></ol><li>c</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_delta() {
+        let markdown = "# Title\n\nSome **bold**, *italic*, ~~gone~~ and `code` with [a link](https://www.test.com).\n\n> quoted\n\n- one\n  - nested\n- [x] done\n- [ ] todo\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n![logo](path/to/logo.png)\n";
+        let expected: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "Title"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "Some "},
+            {"insert": "bold", "attributes": {"bold": true}},
+            {"insert": ", "},
+            {"insert": "italic", "attributes": {"italic": true}},
+            {"insert": ", "},
+            {"insert": "gone", "attributes": {"strike": true}},
+            {"insert": " and "},
+            {"insert": "code", "attributes": {"code": true}},
+            {"insert": " with "},
+            {"insert": "a link", "attributes": {"link": "https://www.test.com"}},
+            {"insert": ".\nquoted"},
+            {"insert": "\n", "attributes": {"blockquote": true}},
+            {"insert": "one"},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "nested"},
+            {"insert": "\n", "attributes": {"list": "bullet", "indent": 1}},
+            {"insert": "done"},
+            {"insert": "\n", "attributes": {"list": "checked"}},
+            {"insert": "todo"},
+            {"insert": "\n", "attributes": {"list": "unchecked"}},
+            {"insert": "fn main() {}"},
+            {"insert": "\n", "attributes": {"code-block": true}},
+            {"insert": "a\tb\n1\t2\n"},
+            {"insert": {"image": "path/to/logo.png"}, "attributes": {"alt": "logo"}},
+            {"insert": "\n"}
+        ]))
+        .unwrap();
+        let delta_ops = markdown_to_delta(markdown);
+        assert_eq!(delta_ops, expected);
+
+        assert_eq!(Schema::default().validate(&delta_ops), Ok(()));
+        let result = parser(delta_ops);
+        assert!(result.contains("<p>Some <strong>bold</strong>"));
+        assert!(result.contains("</p><blockquote>quoted</blockquote><ul><li>one</li><ul><li class=\"ql-indent-1\">nested</li></ul></ul><ul data-checked=\"true\"><li>done</li></ul><ul data-checked=\"false\"><li>todo</li></ul>"));
+
+        // nested task lists and lists of another type
+        assert_eq!(
+            parser(markdown_to_delta("- [x] a\n  - [ ] b\n- c\n  1. d\n")),
+            "<ul data-checked=\"true\"><li>a</li><ul data-checked=\"false\"><li class=\"ql-indent-1\">b</li></ul></ul><ul><li>c</li><ol><li class=\"ql-indent-1\">d</li></ol></ul>"
+        );
+    }
+
+    #[test]
+    fn test_text_to_delta() {
+        let text = "Hi, see www.test.com/docs.\r\n\n- first (https://en.wikipedia.org/wiki/Rust_(language))\n  - mail [alan@fpi.cc]!\n1) one\n\tindented, not@an-email and http:// alone";
+        let expected: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "Hi, see "},
+            {"insert": "www.test.com/docs", "attributes": {"link": "http://www.test.com/docs"}},
+            {"insert": ".\n\nfirst ("},
+            {"insert": "https://en.wikipedia.org/wiki/Rust_(language)", "attributes": {"link": "https://en.wikipedia.org/wiki/Rust_(language)"}},
+            {"insert": ")"},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "mail ["},
+            {"insert": "alan@fpi.cc", "attributes": {"link": "mailto:alan@fpi.cc"}},
+            {"insert": "]!"},
+            {"insert": "\n", "attributes": {"list": "bullet", "indent": 1}},
+            {"insert": "one"},
+            {"insert": "\n", "attributes": {"list": "ordered"}},
+            {"insert": "indented, not@an-email and http:// alone"},
+            {"insert": "\n", "attributes": {"indent": 1}}
+        ]))
+        .unwrap();
+        let delta_ops = text_to_delta(text);
+        assert_eq!(delta_ops, expected);
+        assert_eq!(
+            parser(delta_ops),
+            "<p>Hi, see <a href=\"http://www.test.com/docs\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"http://www.test.com/docs\">www.test.com/docs</a>.</p><p><br></p><ul><li>first (<a href=\"https://en.wikipedia.org/wiki/Rust_(language)\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"https://en.wikipedia.org/wiki/Rust_(language)\">https://en.wikipedia.org/wiki/Rust_(language)</a>)</li><ul><li class=\"ql-indent-1\">mail [<a href=\"mailto:alan@fpi.cc\" rel=\"noopener noreferrer\" target=\"_blank\" title=\"mailto:alan@fpi.cc\">alan@fpi.cc</a>]!</li></ul></ul><ol><li>one</li></ol><p class=\"ql-indent-1\">indented, not@an-email and http:// alone</p>"
+        );
+
+        // a deeper list of another type nests in the open one
+        assert_eq!(
+            parser(text_to_delta("- a\n  1. b\n- c\n  1) d\n2. e\n")),
+            "<ul><li>a</li><ol><li class=\"ql-indent-1\">b</li></ol><li>c</li><ol><li class=\"ql-indent-1\">d</li></ol></ul><ol><li>e</li></ol>"
+        );
+    }
+
+    #[test]
+    fn test_render_to() {
+        let delta_ops: Vec<DeltaOp> = serde_json::from_value(json!([
+            {"insert": "Title"},
+            {"insert": "\n", "attributes": {"header": 1}},
+            {"insert": "hello ", "attributes": {"bold": true}},
+            {"insert": "world", "attributes": {"bold": true, "italic": true}},
+            {"insert": "\none"},
+            {"insert": "\n", "attributes": {"list": "bullet"}},
+            {"insert": "let a = 1;"},
+            {"insert": "\n", "attributes": {"code-block": true}},
+            {"insert": "\n"}
+        ]))
+        .unwrap();
+        let options = RenderOptions::new().header_ids(true);
+        let html = render(&delta_ops, &options);
+        assert_eq!(html, "<h1 id=\"title\">Title</h1><p><strong>hello <em>world</em></strong></p><ul><li>one</li></ul><pre class=\"ql-syntax\" spellcheck=\"false\">let a = 1;\n</pre><p><br></p>");
+
+        let mut bytes: Vec<u8> = Vec::new();
+        render_to(&delta_ops, &options, &mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), html);
+
+        let mut written = String::from("<article>");
+        render_fmt(&delta_ops, &options, &mut written).unwrap();
+        assert_eq!(written, format!("<article>{}", html));
+
+        // a full sink fails with its own error
+        let mut buffer = [0u8; 16];
+        let error = render_to(&delta_ops, &options, &mut buffer[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn test_renderer() {
+        let json = r#"{"ops": [
+            {"insert": "one"},
+            {"insert": "\n", "attributes": {"list": "ordered"}},
+            {"insert": "two"},
+            {"insert": "\n", "attributes": {"list": "ordered"}},
+            {"insert": "end", "attributes": {"italic": true}}
+        ], "version": 1}"#;
+        // a line left without its newline is written before the open lists are closed
+        let expected = "<ol><li>one</li><li>two</li><p><em>end</em></p></ol>";
+        let options = RenderOptions::default();
+
+        let mut bytes: Vec<u8> = Vec::new();
+        render_json(json.as_bytes(), &options, &mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+
+        let delta_ops: Vec<DeltaOp> =
+            serde_json::from_value(serde_json::from_str::<Value>(json).unwrap()["ops"].clone())
+                .unwrap();
+        let array = serde_json::to_string(&delta_ops).unwrap();
+        let mut bytes: Vec<u8> = Vec::new();
+        render_json(array.as_bytes(), &options, &mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+
+        let mut html = String::new();
+        render_iter(delta_ops.clone(), &options, &mut html).unwrap();
+        assert_eq!(html, expected);
+
+        // each line is written once it ends, the list stays open until the next line
+        let mut renderer = Renderer::new(&options);
+        let mut html = String::new();
+        renderer.push(&delta_ops[0], &mut html).unwrap();
+        assert_eq!(html, "");
+        renderer.push(&delta_ops[1], &mut html).unwrap();
+        assert_eq!(html, "<ol><li>one</li>");
+        for op in delta_ops[2..].iter() {
+            renderer.push(op, &mut html).unwrap();
+        }
+        renderer.finish(&mut html).unwrap();
+        assert_eq!(html, expected);
+
+        let error = render_json(&b"[{\"insert\": 1}"[..], &options, Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
 }
@@ -0,0 +1,82 @@
+use serde_json::Value;
+
+use crate::DeltaOp;
+
+// a hyperlink of the document, the `link` attribute rendered as `<a>`
+#[derive(Debug, PartialEq)]
+pub struct Link {
+    pub href: String,
+    /// anchor text, joined over consecutive ops sharing the href
+    pub text: String,
+    /// index of the first op of the link
+    pub op_index: usize,
+    /// character offset of the link in the document, embeds count as one
+    pub offset: usize,
+    /// the anchor text is the url itself, ignoring the scheme and a trailing slash
+    pub text_is_url: bool,
+}
+
+pub fn links(delta_ops: &[DeltaOp]) -> Vec<Link> {
+    let mut links: Vec<Link> = Vec::new();
+    // the link can still be continued by the next op
+    let mut open = false;
+    let mut offset = 0;
+
+    for (op_index, op) in delta_ops.iter().enumerate() {
+        let href = match &op.attributes {
+            Some(Value::Object(attr)) => attr.get("link").and_then(|v| v.as_str()),
+            _ => None,
+        };
+        let text = match &op.insert {
+            Value::String(str_insert) => str_insert.as_str(),
+            _ => "",
+        };
+        let length = match &op.insert {
+            Value::String(str_insert) => str_insert.chars().count(),
+            _ => 1,
+        };
+
+        match href {
+            Some(href) => {
+                // a line break ends the link, quill never links newlines
+                let mut segment_offset = offset;
+                for (index, segment) in text.split('\n').enumerate() {
+                    let continued =
+                        open && index == 0 && links.last().is_some_and(|link| link.href == href);
+                    if continued {
+                        links.last_mut().unwrap().text.push_str(segment);
+                    } else if !segment.is_empty() || text.is_empty() {
+                        // linked embeds have no text
+                        links.push(Link {
+                            href: String::from(href),
+                            text: String::from(segment),
+                            op_index,
+                            offset: segment_offset,
+                            text_is_url: false,
+                        });
+                    }
+                    segment_offset += segment.chars().count() + 1;
+                }
+                open = !text.ends_with('\n');
+            }
+            None => open = false,
+        }
+        offset += length;
+    }
+
+    for link in links.iter_mut() {
+        link.text_is_url = same_url(link.text.trim(), &link.href);
+    }
+    links
+}
+
+fn same_url(text: &str, href: &str) -> bool {
+    fn strip(url: &str) -> &str {
+        let url = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .unwrap_or(url);
+        url.strip_suffix('/').unwrap_or(url)
+    }
+    !text.is_empty() && strip(text).eq_ignore_ascii_case(strip(href))
+}
@@ -0,0 +1,231 @@
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde_json::{Map, Value};
+
+use crate::normalize::normalize;
+use crate::DeltaOp;
+
+/// The delta of a CommonMark document with GFM tables, task lists and strikethrough,
+/// in the form of `normalize`.
+///
+/// Raw html and horizontal rules are dropped, table rows become lines with their cells
+/// separated by tabs.
+pub fn markdown_to_delta(markdown: &str) -> Vec<DeltaOp> {
+    let mut converter = Converter {
+        ops: Vec::new(),
+        inline: Vec::new(),
+        blocks: Vec::new(),
+        lists: Vec::new(),
+        line_started: false,
+        image: None,
+        cell: 0,
+    };
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    for event in Parser::new_ext(markdown, options) {
+        converter.event(event);
+    }
+    if converter.line_started {
+        converter.newline();
+    }
+    normalize(&converter.ops)
+}
+
+struct Converter {
+    ops: Vec<DeltaOp>,
+    // inline formats of the open spans
+    inline: Vec<Map<String, Value>>,
+    // line formats of the open blocks, the innermost applies
+    blocks: Vec<Map<String, Value>>,
+    // `ordered` or `bullet` for each open list
+    lists: Vec<&'static str>,
+    // text or embeds since the last newline
+    line_started: bool,
+    // source and alt text of an image, its description is not content
+    image: Option<(String, String)>,
+    // cells of the current table row so far
+    cell: usize,
+}
+
+impl Converter {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => match &mut self.image {
+                Some((_, alt)) => alt.push_str(&text),
+                None => self.text(&text),
+            },
+            Event::Code(code) => {
+                self.inline.push(format("code", Value::Bool(true)));
+                self.text(&code);
+                self.inline.pop();
+            }
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak => self.newline(),
+            Event::TaskListMarker(checked) => {
+                // the item and, in a loose list, its paragraph
+                let list_type = if checked { "checked" } else { "unchecked" };
+                let indent = self
+                    .blocks
+                    .last()
+                    .and_then(|block| block.get("indent"))
+                    .cloned();
+                for block in self.blocks.iter_mut().rev() {
+                    if !block.contains_key("list") || block.get("indent").cloned() != indent {
+                        break;
+                    }
+                    block.insert(String::from("list"), Value::from(list_type));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.open_block(Map::new()),
+            Tag::Heading { level, .. } => {
+                self.open_block(format("header", Value::from(level as u64)))
+            }
+            Tag::BlockQuote(_) => self.open_block(format("blockquote", Value::Bool(true))),
+            Tag::CodeBlock(_) => {
+                // code lines are never list items or headers
+                self.flush_line();
+                self.blocks.push(format("code-block", Value::Bool(true)));
+            }
+            Tag::List(start) => {
+                self.flush_line();
+                self.lists
+                    .push(if start.is_some() { "ordered" } else { "bullet" });
+            }
+            Tag::Item => {
+                let mut line = format("list", Value::from(*self.lists.last().unwrap_or(&"bullet")));
+                if self.lists.len() > 1 {
+                    line.insert(String::from("indent"), Value::from(self.lists.len() - 1));
+                }
+                self.open_block(line);
+            }
+            Tag::TableHead | Tag::TableRow => {
+                self.open_block(Map::new());
+                self.cell = 0;
+            }
+            Tag::TableCell => {
+                if self.cell > 0 {
+                    self.insert(Value::from("\t"), Map::new());
+                }
+                self.cell += 1;
+            }
+            Tag::Emphasis => self.inline.push(format("italic", Value::Bool(true))),
+            Tag::Strong => self.inline.push(format("bold", Value::Bool(true))),
+            Tag::Strikethrough => self.inline.push(format("strike", Value::Bool(true))),
+            Tag::Link { dest_url, .. } => self
+                .inline
+                .push(format("link", Value::from(dest_url.as_ref()))),
+            Tag::Image { dest_url, .. } => self.image = Some((dest_url.to_string(), String::new())),
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph
+            | TagEnd::Heading(_)
+            | TagEnd::Item
+            | TagEnd::TableHead
+            | TagEnd::TableRow => {
+                // a header is a line even when empty
+                if self.line_started || matches!(tag, TagEnd::Heading(_)) {
+                    self.newline();
+                }
+                self.blocks.pop();
+            }
+            TagEnd::BlockQuote(_) | TagEnd::CodeBlock => {
+                self.flush_line();
+                self.blocks.pop();
+            }
+            TagEnd::List(_) => {
+                self.flush_line();
+                self.lists.pop();
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link => {
+                self.inline.pop();
+            }
+            TagEnd::Image => {
+                if let Some((src, alt)) = self.image.take() {
+                    let attributes = if alt.is_empty() {
+                        Map::new()
+                    } else {
+                        format("alt", Value::from(alt))
+                    };
+                    self.insert(Value::Object(format("image", Value::from(src))), attributes);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // a block inside of a line ends it, the block inherits the line formats around it
+    fn open_block(&mut self, line: Map<String, Value>) {
+        self.flush_line();
+        let mut block = self.blocks.last().cloned().unwrap_or_default();
+        block.extend(line);
+        self.blocks.push(block);
+    }
+
+    fn text(&mut self, text: &str) {
+        if self
+            .blocks
+            .last()
+            .is_some_and(|block| block.contains_key("code-block"))
+        {
+            for segment in text.split_inclusive('\n') {
+                match segment.strip_suffix('\n') {
+                    Some(line) => {
+                        self.insert(Value::from(line), Map::new());
+                        self.newline();
+                    }
+                    None => self.insert(Value::from(segment), Map::new()),
+                }
+            }
+        } else {
+            self.insert(Value::from(text), Map::new());
+        }
+    }
+
+    fn insert(&mut self, insert: Value, attributes: Map<String, Value>) {
+        let mut inline = Map::new();
+        for format in self.inline.iter() {
+            inline.extend(format.clone());
+        }
+        inline.extend(attributes);
+        self.push(insert, inline);
+        self.line_started = true;
+    }
+
+    fn newline(&mut self) {
+        let block = self.blocks.last().cloned().unwrap_or_default();
+        self.push(Value::from("\n"), block);
+        self.line_started = false;
+    }
+
+    fn flush_line(&mut self) {
+        if self.line_started {
+            self.newline();
+        }
+    }
+
+    fn push(&mut self, insert: Value, attributes: Map<String, Value>) {
+        let attributes = if attributes.is_empty() {
+            None
+        } else {
+            Some(Value::Object(attributes))
+        };
+        self.ops.push(DeltaOp { insert, attributes });
+    }
+}
+
+fn format(key: &str, value: Value) -> Map<String, Value> {
+    let mut format = Map::new();
+    format.insert(String::from(key), value);
+    format
+}
@@ -0,0 +1,120 @@
+use serde_json::{Map, Value};
+
+use crate::embed::escape_attr;
+use crate::resolve::MentionResolution;
+use crate::RenderOptions;
+
+// the fields quill-mention always writes, in its dataset order
+const KNOWN_FIELDS: [&str; 4] = ["index", "denotationChar", "id", "value"];
+
+pub(crate) fn format(mention: &Map<String, Value>, options: &RenderOptions) -> String {
+    let resolution = match &options.mention_resolver {
+        Some(mention_resolver) => mention_resolver(mention),
+        None => MentionResolution::Keep,
+    };
+    match resolution {
+        MentionResolution::Keep => format_resolved(mention, options),
+        MentionResolution::Replace(mention) => format_resolved(&mention, options),
+        MentionResolution::Text => format!(
+            "{}{}",
+            escape_attr(&field(mention, "denotationChar").unwrap_or_else(|| String::from("@"))),
+            escape_attr(&field(mention, "value").unwrap_or_default())
+        ),
+        MentionResolution::Remove => String::from(""),
+    }
+}
+
+fn format_resolved(mention: &Map<String, Value>, options: &RenderOptions) -> String {
+    if let Some(mention_renderer) = &options.mention_renderer {
+        return mention_renderer(mention);
+    }
+
+    let denotation_char =
+        escape_attr(&field(mention, "denotationChar").unwrap_or_else(|| String::from("@")));
+    let mention_value = escape_attr(&field(mention, "value").unwrap_or_default());
+    let data_attributes = data_attributes(mention);
+
+    match &options.mention_href {
+        Some(template) => format!(
+            "<a href=\"{}\" class=\"mention\"{}>{}{}</a>",
+            escape_attr(&fill(template, mention)),
+            data_attributes,
+            denotation_char,
+            mention_value
+        ),
+        None => format!("<span class=\"mention\"{}>&#xFEFF;<span contenteditable=\"false\"><span class=\"{}mention-denotation-char\">{}</span>{}</span>&#xFEFF;</span>", data_attributes, options.class_prefix, denotation_char, mention_value),
+    }
+}
+
+/// Every scalar field of a mention as ` data-*` attributes, `denotationChar` becomes
+/// `data-denotation-char`. Fields whose names are not made of ascii letters, digits,
+/// `_` and `-` are left out.
+pub fn data_attributes(mention: &Map<String, Value>) -> String {
+    let mut keys: Vec<&str> = KNOWN_FIELDS.to_vec();
+    keys.extend(
+        mention
+            .keys()
+            .map(|key| key.as_str())
+            .filter(|key| !KNOWN_FIELDS.contains(key) && is_attribute_name(key)),
+    );
+
+    keys.iter()
+        .map(|key| {
+            // quill-mention always writes the known fields, even when empty
+            let value = field(mention, key);
+            if value.is_none() && !KNOWN_FIELDS.contains(key) {
+                return String::from("");
+            }
+            let value = match (*key, value) {
+                (_, Some(value)) => value,
+                ("denotationChar", None) => String::from("@"),
+                (_, None) => String::from(""),
+            };
+            format!(" data-{}=\"{}\"", kebab_case(key), escape_attr(&value))
+        })
+        .collect::<String>()
+}
+
+// field names end up unescaped in attribute names
+fn is_attribute_name(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn field(mention: &Map<String, Value>, key: &str) -> Option<String> {
+    match mention.get(key) {
+        Some(Value::String(value)) => Some(value.clone()),
+        Some(Value::Number(value)) => Some(value.to_string()),
+        Some(Value::Bool(value)) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+// "/users/{id}" => "/users/96"
+fn fill(template: &str, mention: &Map<String, Value>) -> String {
+    let mut result = String::from(template);
+    for key in mention.keys() {
+        let placeholder = format!("{{{}}}", key);
+        if result.contains(&placeholder) {
+            result = result.replace(&placeholder, &field(mention, key).unwrap_or_default());
+        }
+    }
+    result
+}
+
+fn kebab_case(key: &str) -> String {
+    let mut result = String::new();
+    for char in key.chars() {
+        if char.is_uppercase() {
+            result.push('-');
+            result.extend(char.to_lowercase());
+        } else if char == '_' {
+            result.push('-');
+        } else {
+            result.push(char);
+        }
+    }
+    result
+}
@@ -0,0 +1,111 @@
+use serde_json::{Map, Value};
+
+use crate::block_format::BLOCK_ATTRIBUTES;
+use crate::DeltaOp;
+
+// canonical minimal form of a delta: no empty inserts, no null or false attributes,
+// line formats only on newlines and inline formats only on text and embeds, adjacent
+// ops with the same attributes merged, and a trailing newline
+pub fn normalize(delta_ops: &[DeltaOp]) -> Vec<DeltaOp> {
+    let mut normalized: Vec<DeltaOp> = Vec::new();
+
+    for op in delta_ops.iter() {
+        let attr = match &op.attributes {
+            Some(Value::Object(attr)) => clean(attr),
+            _ => Map::new(),
+        };
+        match &op.insert {
+            Value::String(str_insert) => {
+                for segment in str_insert.split_inclusive('\n') {
+                    let (text, newline) = match segment.strip_suffix('\n') {
+                        Some(text) => (text, true),
+                        None => (segment, false),
+                    };
+                    if !text.is_empty() {
+                        push(
+                            &mut normalized,
+                            Value::String(String::from(text)),
+                            inline(&attr),
+                        );
+                    }
+                    if newline {
+                        push(
+                            &mut normalized,
+                            Value::String(String::from("\n")),
+                            block(&attr),
+                        );
+                    }
+                }
+            }
+            Value::Object(obj_insert) if !obj_insert.is_empty() => {
+                push(&mut normalized, op.insert.clone(), inline(&attr));
+            }
+            _ => (),
+        }
+    }
+
+    let ends_with_newline = match normalized.last() {
+        Some(DeltaOp {
+            insert: Value::String(str_insert),
+            ..
+        }) => str_insert.ends_with('\n'),
+        _ => false,
+    };
+    if !ends_with_newline {
+        push(
+            &mut normalized,
+            Value::String(String::from("\n")),
+            Map::new(),
+        );
+    }
+    normalized
+}
+
+// drop null and false values, they mean "no format"
+fn clean(attr: &Map<String, Value>) -> Map<String, Value> {
+    attr.iter()
+        .filter(|(key, value)| match value {
+            Value::Null | Value::Bool(false) => false,
+            Value::Number(number) => !(key.as_str() == "indent" && number.as_u64() == Some(0)),
+            _ => true,
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn block(attr: &Map<String, Value>) -> Map<String, Value> {
+    attr.iter()
+        .filter(|(key, _)| BLOCK_ATTRIBUTES.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn inline(attr: &Map<String, Value>) -> Map<String, Value> {
+    attr.iter()
+        .filter(|(key, _)| !BLOCK_ATTRIBUTES.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+// like quill's `Delta.push`, text is merged into the previous op with the same attributes
+fn push(normalized: &mut Vec<DeltaOp>, insert: Value, attr: Map<String, Value>) {
+    let attributes = if attr.is_empty() {
+        None
+    } else {
+        Some(Value::Object(attr))
+    };
+
+    if let Value::String(str_insert) = &insert {
+        if let Some(DeltaOp {
+            insert: Value::String(last_insert),
+            attributes: last_attributes,
+        }) = normalized.last_mut()
+        {
+            if *last_attributes == attributes {
+                last_insert.push_str(str_insert);
+                return;
+            }
+        }
+    }
+    normalized.push(DeltaOp { insert, attributes });
+}
@@ -0,0 +1,254 @@
+use serde_json::{Map, Value};
+
+use crate::asset::AssetKind;
+use crate::attachment::AttachmentTable;
+use crate::resolve::{LinkResolution, MentionResolution};
+
+type LinkPolicyFor = Box<dyn Fn(&str) -> LinkPolicy + Send + Sync>;
+type FormulaRenderer = Box<dyn Fn(&str) -> String + Send + Sync>;
+type MentionRenderer = Box<dyn Fn(&Map<String, Value>) -> String + Send + Sync>;
+type MentionResolver = Box<dyn Fn(&Map<String, Value>) -> MentionResolution + Send + Sync>;
+type LinkResolver = Box<dyn Fn(&str) -> LinkResolution + Send + Sync>;
+type UrlRewriter =
+    Box<dyn Fn(AssetKind, &str, &Map<String, Value>) -> Option<String> + Send + Sync>;
+
+// attributes of rendered links besides the href
+#[derive(Clone, PartialEq)]
+pub struct LinkPolicy {
+    pub(crate) target: Option<String>,
+    pub(crate) rel: Option<String>,
+    pub(crate) title: bool,
+}
+
+impl Default for LinkPolicy {
+    /// `rel="noopener noreferrer" target="_blank" title="{href}"`
+    fn default() -> Self {
+        LinkPolicy {
+            target: Some(String::from("_blank")),
+            rel: Some(String::from("noopener noreferrer")),
+            title: true,
+        }
+    }
+}
+
+impl LinkPolicy {
+    /// Same tab navigation without rel or title, e.g. for internal links.
+    pub fn same_tab() -> LinkPolicy {
+        LinkPolicy {
+            target: None,
+            rel: None,
+            title: false,
+        }
+    }
+
+    pub fn target(mut self, target: Option<&str>) -> Self {
+        self.target = target.map(String::from);
+        self
+    }
+
+    /// e.g. `Some("nofollow ugc noopener")` for user content.
+    pub fn rel(mut self, rel: Option<&str>) -> Self {
+        self.rel = rel.map(String::from);
+        self
+    }
+
+    /// Repeat the href as the link title.
+    pub fn title(mut self, title: bool) -> Self {
+        self.title = title;
+        self
+    }
+}
+
+// how quill stores the size and font formats
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Attributor {
+    /// `ql-size-large`, quill's default with `small|large|huge` and `serif|monospace`
+    Class,
+    /// `font-size: 30px`, any css value
+    Style,
+}
+
+// render options, `RenderOptions::default()` renders exactly what `parser` always did
+pub struct RenderOptions {
+    pub(crate) paragraph_tag: String,
+    pub(crate) empty_line: String,
+    pub(crate) class_prefix: String,
+    pub(crate) code_block_class: String,
+    pub(crate) inline_styles: bool,
+    pub(crate) size_attributor: Attributor,
+    pub(crate) font_attributor: Attributor,
+    pub(crate) header_ids: bool,
+    pub(crate) formula_renderer: Option<FormulaRenderer>,
+    pub(crate) attachment_table: AttachmentTable,
+    pub(crate) mention_href: Option<String>,
+    pub(crate) mention_renderer: Option<MentionRenderer>,
+    pub(crate) mention_resolver: Option<MentionResolver>,
+    pub(crate) link_resolver: Option<LinkResolver>,
+    pub(crate) url_rewriter: Option<UrlRewriter>,
+    pub(crate) link_policy: LinkPolicy,
+    pub(crate) link_policy_for: Option<LinkPolicyFor>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            paragraph_tag: String::from("p"),
+            empty_line: String::from("<br>"),
+            class_prefix: String::from("ql-"),
+            code_block_class: String::from("ql-syntax"),
+            inline_styles: false,
+            size_attributor: Attributor::Style,
+            font_attributor: Attributor::Style,
+            header_ids: false,
+            formula_renderer: None,
+            attachment_table: AttachmentTable::default(),
+            mention_href: None,
+            mention_renderer: None,
+            mention_resolver: None,
+            link_resolver: None,
+            url_rewriter: None,
+            link_policy: LinkPolicy::default(),
+            link_policy_for: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    pub fn new() -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    /// Tag of normal lines, `p` by default.
+    pub fn paragraph_tag(mut self, paragraph_tag: &str) -> Self {
+        self.paragraph_tag = String::from(paragraph_tag);
+        self
+    }
+
+    /// Content of an empty line, `<br>` by default.
+    pub fn empty_line(mut self, empty_line: &str) -> Self {
+        self.empty_line = String::from(empty_line);
+        self
+    }
+
+    /// Prefix of the quill classes (`ql-align-center`, `ql-indent-1`, `ql-video`...),
+    /// `ql-` by default.
+    pub fn class_prefix(mut self, class_prefix: &str) -> Self {
+        self.class_prefix = String::from(class_prefix);
+        self
+    }
+
+    /// Class of the `<pre>` of code blocks, `ql-syntax` by default. Empty for no class.
+    pub fn code_block_class(mut self, code_block_class: &str) -> Self {
+        self.code_block_class = String::from(code_block_class);
+        self
+    }
+
+    /// Emit inline `style` declarations instead of `ql-*` classes, for html shown
+    /// without quill's stylesheet such as emails.
+    pub fn inline_styles(mut self, inline_styles: bool) -> Self {
+        self.inline_styles = inline_styles;
+        self
+    }
+
+    /// How `size` values are rendered, `Attributor::Style` by default. With inline styles,
+    /// class values are written as the css of quill's stylesheet.
+    pub fn size_attributor(mut self, size_attributor: Attributor) -> Self {
+        self.size_attributor = size_attributor;
+        self
+    }
+
+    /// How `font` values are rendered, `Attributor::Style` by default.
+    pub fn font_attributor(mut self, font_attributor: Attributor) -> Self {
+        self.font_attributor = font_attributor;
+        self
+    }
+
+    /// Shorthand for the `target` of the link policy, `None` for same tab navigation.
+    pub fn link_target(mut self, target: Option<&str>) -> Self {
+        self.link_policy = self.link_policy.target(target);
+        self
+    }
+
+    /// Emit an `id` on every `<hN>`, matching the anchors produced by `toc::toc`.
+    pub fn header_ids(mut self, header_ids: bool) -> Self {
+        self.header_ids = header_ids;
+        self
+    }
+
+    /// Render the tex source of formula embeds on the server, e.g. into MathML.
+    /// The result is placed inside `<span class="ql-formula">`.
+    pub fn formula_renderer<F>(mut self, formula_renderer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.formula_renderer = Some(Box::new(formula_renderer));
+        self
+    }
+
+    /// Decide how `savvy_attach` embeds render: image, video, audio or download link.
+    pub fn attachment_table(mut self, attachment_table: AttachmentTable) -> Self {
+        self.attachment_table = attachment_table;
+        self
+    }
+
+    /// Render mentions as links, `{field}` placeholders are filled from the mention,
+    /// e.g. `"/users/{id}"`.
+    pub fn mention_href(mut self, template: &str) -> Self {
+        self.mention_href = Some(String::from(template));
+        self
+    }
+
+    /// Render mentions with a callback receiving every mention field,
+    /// `mention::data_attributes` gives the default `data-*` attributes.
+    pub fn mention_renderer<F>(mut self, mention_renderer: F) -> Self
+    where
+        F: Fn(&Map<String, Value>) -> String + Send + Sync + 'static,
+    {
+        self.mention_renderer = Some(Box::new(mention_renderer));
+        self
+    }
+
+    /// Look up every mention embed before it is rendered, e.g. against a user directory.
+    pub fn mention_resolver<F>(mut self, mention_resolver: F) -> Self
+    where
+        F: Fn(&Map<String, Value>) -> MentionResolution + Send + Sync + 'static,
+    {
+        self.mention_resolver = Some(Box::new(mention_resolver));
+        self
+    }
+
+    /// Look up every `link` href before it is rendered, e.g. to canonicalize internal urls.
+    pub fn link_resolver<F>(mut self, link_resolver: F) -> Self
+    where
+        F: Fn(&str) -> LinkResolution + Send + Sync + 'static,
+    {
+        self.link_resolver = Some(Box::new(link_resolver));
+        self
+    }
+
+    /// Rewrite the url of every image, attachment and video embed, e.g. from a storage
+    /// path to a signed CDN url. Returning None drops the embed.
+    pub fn url_rewriter<F>(mut self, url_rewriter: F) -> Self
+    where
+        F: Fn(AssetKind, &str, &Map<String, Value>) -> Option<String> + Send + Sync + 'static,
+    {
+        self.url_rewriter = Some(Box::new(url_rewriter));
+        self
+    }
+
+    /// Target, rel and title of every link.
+    pub fn link_policy(mut self, link_policy: LinkPolicy) -> Self {
+        self.link_policy = link_policy;
+        self
+    }
+
+    /// Pick the link policy per href, e.g. same tab for internal links only.
+    /// Takes precedence over `link_policy`.
+    pub fn link_policy_for<F>(mut self, link_policy_for: F) -> Self
+    where
+        F: Fn(&str) -> LinkPolicy + Send + Sync + 'static,
+    {
+        self.link_policy_for = Some(Box::new(link_policy_for));
+        self
+    }
+}
@@ -0,0 +1,182 @@
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde_json::Value;
+use std::fmt::{self, Write};
+
+use crate::block_format::{header_level, BlockState, LineFormat};
+use crate::embed::{self, Embed};
+use crate::inline_format::{self, InlineRun};
+use crate::toc::Anchors;
+use crate::{DeltaOp, RenderOptions};
+
+/// Renders ops pushed one at a time, writing each line as soon as it ends. Only the
+/// current line and the open lists are kept in between.
+pub struct Renderer<'a> {
+    options: &'a RenderOptions,
+    // runs of the current line, formatted together when the line ends
+    reader: Vec<InlineRun>,
+    // plain text of the current line, used for header anchors
+    line_text: String,
+    anchors: Anchors,
+    block_state: BlockState,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(options: &'a RenderOptions) -> Renderer<'a> {
+        Renderer {
+            options,
+            reader: Vec::new(),
+            line_text: String::from(""),
+            anchors: Anchors::new(),
+            block_state: BlockState::new(),
+        }
+    }
+
+    pub fn push<W: Write>(&mut self, op: &DeltaOp, out: &mut W) -> fmt::Result {
+        let options = self.options;
+        let reader = &mut self.reader;
+        let line_text = &mut self.line_text;
+        let anchors = &mut self.anchors;
+        let block_state = &mut self.block_state;
+
+        if let Value::String(str_insert) = &op.insert {
+            let mut inner_reader = String::from("");
+            for (_, char) in str_insert.char_indices() {
+                if char != '\n' {
+                    // scan&store all content before a line break
+                    inner_reader.push(char);
+                    line_text.push(char);
+                } else {
+                    if !inner_reader.is_empty() {
+                        reader.push(InlineRun::raw(inner_reader.clone()));
+                        inner_reader.clear();
+                    }
+                    // runs are never empty, an empty line has none
+                    let content = |out: &mut W| inline_format::write_runs(out, reader);
+                    let tmp_content = |out: &mut W| {
+                        if reader.is_empty() {
+                            out.write_str(&options.empty_line)
+                        } else {
+                            inline_format::write_runs(out, reader)
+                        }
+                    };
+
+                    if let Some(Value::Object(attr)) = &op.attributes {
+                        // need check if there has a intent attr
+                        if let Some(Value::String(list_type)) = attr.get("list") {
+                            block_state.write_open_block(out, attr, list_type, content, options)?;
+                        } else if attr.get("code-block").is_some() {
+                            block_state.write_open_block(
+                                out,
+                                attr,
+                                "code-block",
+                                content,
+                                options,
+                            )?;
+                        } else if attr.get("blockquote").is_some() {
+                            block_state.write_close_current_block(out)?;
+                            out.write_str("<blockquote")?;
+                            LineFormat::from_attr(attr).write_html_attr(out, options, false)?;
+                            out.write_char('>')?;
+                            tmp_content(out)?;
+                            out.write_str("</blockquote>")?;
+                        } else if let Some(header) = header_level(attr) {
+                            block_state.write_close_current_block(out)?;
+                            write!(out, "<h{}", header)?;
+                            if options.header_ids {
+                                write!(out, " id=\"{}\"", anchors.next(line_text))?;
+                            }
+                            LineFormat::from_attr(attr).write_html_attr(out, options, false)?;
+                            out.write_char('>')?;
+                            tmp_content(out)?;
+                            write!(out, "</h{}>", header)?;
+                        } else {
+                            // indent, align, direction or no line format at all
+                            block_state.write_close_current_block(out)?;
+                            write!(out, "<{}", options.paragraph_tag)?;
+                            LineFormat::from_attr(attr).write_html_attr(out, options, false)?;
+                            out.write_char('>')?;
+                            tmp_content(out)?;
+                            write!(out, "</{}>", options.paragraph_tag)?;
+                        }
+                    } else {
+                        block_state.write_close_current_block(out)?;
+                        write!(out, "<{}>", options.paragraph_tag)?;
+                        tmp_content(out)?;
+                        write!(out, "</{}>", options.paragraph_tag)?;
+                    }
+                    reader.clear();
+                    line_text.clear();
+                }
+            }
+            // can not find a line break in this op
+            // try format the content with attr(if exist)
+            if !inner_reader.is_empty() {
+                reader.push(InlineRun::new(inner_reader, &op.attributes, options));
+            }
+        } else if let Value::Object(obj_insert) = &op.insert {
+            if let Some(embed) = Embed::from_insert(obj_insert) {
+                let content = embed::format(&embed, &op.attributes, options);
+                if !content.is_empty() {
+                    reader.push(InlineRun::raw(content));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the last line when it has no newline, as a paragraph, and close the open
+    /// lists.
+    pub fn finish<W: Write>(mut self, out: &mut W) -> fmt::Result {
+        if !self.reader.is_empty() {
+            write!(out, "<{}>", self.options.paragraph_tag)?;
+            inline_format::write_runs(out, &self.reader)?;
+            write!(out, "</{}>", self.options.paragraph_tag)?;
+        }
+        self.block_state.write_close_current_block(out)
+    }
+}
+
+// pushes the ops of a json array, or of the `ops` of a delta, as they are deserialized
+pub(crate) struct OpsSeed<'r, 'a, W> {
+    pub(crate) renderer: &'r mut Renderer<'a>,
+    pub(crate) out: &'r mut W,
+}
+
+impl<'de, 'r, 'a, W: Write> DeserializeSeed<'de> for OpsSeed<'r, 'a, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'r, 'a, W: Write> Visitor<'de> for OpsSeed<'r, 'a, W> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of ops or a delta")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(op) = seq.next_element::<DeltaOp>()? {
+            self.renderer
+                .push(&op, self.out)
+                .map_err(|_| de::Error::custom("failed to write the html"))?;
+        }
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut seed = Some(self);
+        while let Some(key) = map.next_key::<String>()? {
+            match (key.as_str(), seed.take()) {
+                ("ops", Some(ops_seed)) => map.next_value_seed(ops_seed)?,
+                (_, unused) => {
+                    seed = unused;
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
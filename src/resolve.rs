@@ -0,0 +1,27 @@
+use serde_json::{Map, Value};
+
+// what a mention resolver decides for each mention embed
+pub enum MentionResolution {
+    /// Render the mention as it is stored.
+    Keep,
+    /// Render with these fields instead, e.g. the current display name in `value`.
+    /// Extra fields become `data-*` attributes.
+    Replace(Map<String, Value>),
+    /// Render the denotation char and value as plain text.
+    Text,
+    /// Drop the mention, e.g. the user was deleted.
+    Remove,
+}
+
+// what a link resolver decides for each `link` attribute
+pub enum LinkResolution {
+    /// Render the link as it is stored.
+    Keep,
+    /// Render with another href and extra attributes on the `<a>`.
+    Replace {
+        href: String,
+        attributes: Vec<(String, String)>,
+    },
+    /// Render the linked content without the `<a>`.
+    Text,
+}
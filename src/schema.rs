@@ -0,0 +1,275 @@
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::DeltaOp;
+
+// accepted values of an attribute
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValueType {
+    /// `true`, e.g. `bold`, or `false` which removes the format like null
+    True,
+    String,
+    Number,
+    /// `#fff`, `#242729`, `rgb(...)`, `var(--black-075)` or a color name
+    Color,
+    /// a relative url or an http, https, mailto or tel url
+    Url,
+    OneOf(Vec<String>),
+    /// an integer in the inclusive range
+    Range(i64, i64),
+    AnyOf(Vec<ValueType>),
+}
+
+impl ValueType {
+    fn accepts(&self, value: &Value) -> bool {
+        match (self, value) {
+            (ValueType::True, Value::Bool(_)) => true,
+            (ValueType::String, Value::String(_)) => true,
+            (ValueType::Number, Value::Number(_)) => true,
+            (ValueType::Color, Value::String(color)) => is_color(color),
+            (ValueType::Url, Value::String(url)) => is_url(url),
+            (ValueType::OneOf(values), Value::String(value)) => values.contains(value),
+            (ValueType::Range(min, max), Value::Number(number)) => number
+                .as_i64()
+                .is_some_and(|number| *min <= number && number <= *max),
+            (ValueType::AnyOf(value_types), value) => value_types
+                .iter()
+                .any(|value_type| value_type.accepts(value)),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ViolationKind {
+    UnknownAttribute(String),
+    InvalidValue {
+        attribute: String,
+        value: Value,
+    },
+    UnknownEmbed(String),
+    /// neither a string nor an embed object
+    InvalidInsert,
+    TooLong {
+        length: usize,
+        max_length: usize,
+    },
+    MissingTrailingNewline,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Violation {
+    /// the op the violation was found in, the last op for document level violations
+    pub op_index: usize,
+    pub kind: ViolationKind,
+}
+
+// allowed formats and embeds of a document
+pub struct Schema {
+    attributes: HashMap<String, ValueType>,
+    embeds: Vec<String>,
+    max_length: Option<usize>,
+    trailing_newline: bool,
+}
+
+fn one_of(values: &[&str]) -> ValueType {
+    ValueType::OneOf(values.iter().map(|value| String::from(*value)).collect())
+}
+
+impl Default for Schema {
+    /// Everything `parser` renders.
+    fn default() -> Self {
+        let dimension = ValueType::AnyOf(vec![ValueType::String, ValueType::Number]);
+        Schema::new()
+            .attribute("bold", ValueType::True)
+            .attribute("italic", ValueType::True)
+            .attribute("underline", ValueType::True)
+            .attribute("strike", ValueType::True)
+            .attribute("code", ValueType::True)
+            .attribute("script", one_of(&["super", "sub"]))
+            .attribute("link", ValueType::Url)
+            .attribute("color", ValueType::Color)
+            .attribute("background", ValueType::Color)
+            // font size on text, byte size on attachments
+            .attribute("size", dimension.clone())
+            .attribute("font", ValueType::String)
+            .attribute("alt", ValueType::String)
+            .attribute("width", dimension.clone())
+            .attribute("height", dimension)
+            .attribute("style", ValueType::String)
+            .attribute("type", ValueType::String)
+            .attribute("name", ValueType::String)
+            .attribute("header", ValueType::Range(1, 6))
+            .attribute(
+                "list",
+                one_of(&["ordered", "bullet", "checked", "unchecked"]),
+            )
+            .attribute(
+                "code-block",
+                ValueType::AnyOf(vec![ValueType::True, ValueType::String]),
+            )
+            .attribute("blockquote", ValueType::True)
+            .attribute("align", one_of(&["left", "center", "right", "justify"]))
+            .attribute("indent", ValueType::Range(0, 8))
+            .attribute("direction", one_of(&["rtl"]))
+            .embed("image")
+            .embed("savvy_image")
+            .embed("savvy_attach")
+            .embed("mention")
+            .embed("video")
+            .embed("formula")
+            .trailing_newline(true)
+    }
+}
+
+impl Schema {
+    /// A schema allowing no attributes and no embeds.
+    pub fn new() -> Schema {
+        Schema {
+            attributes: HashMap::new(),
+            embeds: Vec::new(),
+            max_length: None,
+            trailing_newline: false,
+        }
+    }
+
+    pub fn attribute(mut self, name: &str, value_type: ValueType) -> Self {
+        self.attributes.insert(String::from(name), value_type);
+        self
+    }
+
+    pub fn embed(mut self, name: &str) -> Self {
+        self.embeds.push(String::from(name));
+        self
+    }
+
+    /// Maximum length in characters, embeds count as one.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Require the document to end with a newline, as quill always does.
+    pub fn trailing_newline(mut self, trailing_newline: bool) -> Self {
+        self.trailing_newline = trailing_newline;
+        self
+    }
+
+    pub fn validate(&self, delta_ops: &[DeltaOp]) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+        let mut length = 0;
+
+        for (op_index, op) in delta_ops.iter().enumerate() {
+            match &op.insert {
+                Value::String(str_insert) => length += str_insert.chars().count(),
+                Value::Object(obj_insert) if obj_insert.len() == 1 => {
+                    length += 1;
+                    let name = obj_insert.keys().next().unwrap();
+                    if !self.embeds.contains(name) {
+                        violations.push(Violation {
+                            op_index,
+                            kind: ViolationKind::UnknownEmbed(name.clone()),
+                        });
+                    }
+                }
+                _ => violations.push(Violation {
+                    op_index,
+                    kind: ViolationKind::InvalidInsert,
+                }),
+            }
+
+            if let Some(Value::Object(attr)) = &op.attributes {
+                for (key, value) in attr {
+                    // null removes a format
+                    if value.is_null() {
+                        continue;
+                    }
+                    match self.attributes.get(key) {
+                        Some(value_type) if value_type.accepts(value) => (),
+                        Some(_) => violations.push(Violation {
+                            op_index,
+                            kind: ViolationKind::InvalidValue {
+                                attribute: key.clone(),
+                                value: value.clone(),
+                            },
+                        }),
+                        None => violations.push(Violation {
+                            op_index,
+                            kind: ViolationKind::UnknownAttribute(key.clone()),
+                        }),
+                    }
+                }
+            }
+        }
+
+        let last_index = delta_ops.len().saturating_sub(1);
+        if let Some(max_length) = self.max_length {
+            if length > max_length {
+                violations.push(Violation {
+                    op_index: last_index,
+                    kind: ViolationKind::TooLong { length, max_length },
+                });
+            }
+        }
+        if self.trailing_newline {
+            let ends_with_newline = match delta_ops.last() {
+                Some(DeltaOp {
+                    insert: Value::String(str_insert),
+                    ..
+                }) => str_insert.ends_with('\n'),
+                _ => false,
+            };
+            if !ends_with_newline {
+                violations.push(Violation {
+                    op_index: last_index,
+                    kind: ViolationKind::MissingTrailingNewline,
+                });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn is_color(color: &str) -> bool {
+    let color = color.trim();
+    if let Some(hex) = color.strip_prefix('#') {
+        return [3, 4, 6, 8].contains(&hex.len()) && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+    for function in ["rgb(", "rgba(", "hsl(", "hsla("].iter() {
+        if let Some(arguments) = color.strip_prefix(function) {
+            return arguments.strip_suffix(')').is_some_and(|arguments| {
+                arguments
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || ['.', ',', '%', ' '].contains(&c))
+            });
+        }
+    }
+    if let Some(name) = color.strip_prefix("var(--") {
+        return name.strip_suffix(')').is_some_and(|name| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+    }
+    !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_url(url: &str) -> bool {
+    if url.is_empty() || url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return false;
+    }
+    // a scheme is everything before the first ':' when no '/', '?' or '#' comes first
+    match url.find([':', '/', '?', '#']) {
+        Some(index) if url[index..].starts_with(':') => {
+            let scheme = url[..index].to_lowercase();
+            ["http", "https", "mailto", "tel"].contains(&scheme.as_str())
+        }
+        _ => true,
+    }
+}
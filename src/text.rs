@@ -0,0 +1,167 @@
+use serde_json::{Map, Value};
+
+use crate::normalize::normalize;
+use crate::DeltaOp;
+
+// quill's highest indent
+const MAX_INDENT: usize = 8;
+
+// what may come before and after a url or an email in running text
+const OPENING_PUNCTUATION: [char; 5] = ['(', '<', '[', '"', '\''];
+const CLOSING_PUNCTUATION: [char; 11] = ['.', ',', ';', ':', '!', '?', ')', '>', ']', '"', '\''];
+
+/// The delta of plain text, in the form of `normalize`.
+///
+/// Every line is a paragraph. Lines starting with `- `, `* ` or `+ ` are bullet list
+/// items, lines starting with `1. ` or `1) ` ordered ones, and a leading tab or two
+/// spaces is one level of indent. Urls and emails are linked.
+pub fn text_to_delta(text: &str) -> Vec<DeltaOp> {
+    let mut delta_ops: Vec<DeltaOp> = Vec::new();
+    for line in text.lines() {
+        let (line, attributes) = line_format(line);
+        for (segment, href) in autolink(line) {
+            let attributes = href.map(|href| {
+                let mut link = Map::new();
+                link.insert(String::from("link"), Value::from(href));
+                Value::Object(link)
+            });
+            delta_ops.push(DeltaOp {
+                insert: Value::from(segment),
+                attributes,
+            });
+        }
+
+        delta_ops.push(DeltaOp {
+            insert: Value::from("\n"),
+            attributes: Some(Value::Object(attributes)),
+        });
+    }
+    normalize(&delta_ops)
+}
+
+// the line without its indentation and list marker, and its line formats
+fn line_format(line: &str) -> (&str, Map<String, Value>) {
+    let mut attributes = Map::new();
+    let content = line.trim_start_matches([' ', '\t']);
+    let indentation = &line[..line.len() - content.len()];
+    let indent = indentation.matches('\t').count() + indentation.matches(' ').count() / 2;
+    if indent > 0 {
+        attributes.insert(String::from("indent"), Value::from(indent.min(MAX_INDENT)));
+    }
+
+    for marker in ["- ", "* ", "+ "].iter() {
+        if let Some(item) = content.strip_prefix(marker) {
+            attributes.insert(String::from("list"), Value::from("bullet"));
+            return (item, attributes);
+        }
+    }
+    let digits = content.len()
+        - content
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    if digits > 0 && digits < 10 {
+        let rest = &content[digits..];
+        if let Some(item) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            attributes.insert(String::from("list"), Value::from("ordered"));
+            return (item, attributes);
+        }
+    }
+    (content, attributes)
+}
+
+// the text split around its urls and emails, with the href of each of them
+fn autolink(text: &str) -> Vec<(&str, Option<String>)> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for (word_start, word) in words(text) {
+        let trimmed = word.trim_start_matches(OPENING_PUNCTUATION);
+        let link_start = word_start + word.len() - trimmed.len();
+        let link = trim_closing(trimmed);
+        let href = match href(link) {
+            Some(href) => href,
+            None => continue,
+        };
+        if start < link_start {
+            segments.push((&text[start..link_start], None));
+        }
+        segments.push((link, Some(href)));
+        start = link_start + link.len();
+    }
+    if start < text.len() {
+        segments.push((&text[start..], None));
+    }
+    segments
+}
+
+// the words of a text with their byte offsets
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (index, char) in text.char_indices() {
+        match (char.is_whitespace(), word_start) {
+            (true, Some(start)) => {
+                words.push((start, &text[start..index]));
+                word_start = None;
+            }
+            (false, None) => word_start = Some(index),
+            _ => (),
+        }
+    }
+    if let Some(start) = word_start {
+        words.push((start, &text[start..]));
+    }
+    words
+}
+
+// "(https://en.wikipedia.org/wiki/Rust_(language))." => "...Rust_(language)"
+fn trim_closing(link: &str) -> &str {
+    let mut link = link;
+    while let Some(last) = link.chars().next_back() {
+        let balanced = last == ')' && link.matches('(').count() >= link.matches(')').count();
+        if !CLOSING_PUNCTUATION.contains(&last) || balanced {
+            break;
+        }
+        link = &link[..link.len() - last.len_utf8()];
+    }
+    link
+}
+
+fn href(link: &str) -> Option<String> {
+    let lowercase = link.to_lowercase();
+    for scheme in ["https://", "http://"].iter() {
+        if lowercase.starts_with(scheme) && is_host(&link[scheme.len()..]) {
+            return Some(String::from(link));
+        }
+    }
+    if lowercase.starts_with("www.") && is_host(&link[4..]) {
+        return Some(format!("http://{}", link));
+    }
+    if is_email(link) {
+        return Some(format!("mailto:{}", link));
+    }
+    None
+}
+
+// at least a name before the path
+fn is_host(rest: &str) -> bool {
+    rest.split(['/', '?', '#'])
+        .next()
+        .is_some_and(|host| !host.is_empty())
+}
+
+fn is_email(link: &str) -> bool {
+    let (local, domain) = match link.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let local_valid = !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || ['.', '_', '%', '+', '-'].contains(&c));
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_valid = labels.len() > 1
+        && labels.iter().all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    local_valid && domain_valid
+}
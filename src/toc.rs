@@ -0,0 +1,139 @@
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::block_format::header_level;
+use crate::embed::escape_attr;
+use crate::DeltaOp;
+
+// table of contents
+pub struct TocEntry {
+    pub level: u64,
+    pub text: String,
+    pub id: String,
+    /// index of the op the header line starts in
+    pub op_index: usize,
+    /// character offset of the header line in the document, embeds count as one
+    pub offset: usize,
+    pub children: Vec<TocEntry>,
+}
+
+// generate unique anchor ids, "intro", "intro-1", "intro-2"...
+pub(crate) struct Anchors {
+    used: HashSet<String>,
+    // the next suffix to try for each slug
+    suffixes: HashMap<String, usize>,
+}
+
+impl Anchors {
+    pub(crate) fn new() -> Anchors {
+        Anchors {
+            used: HashSet::new(),
+            suffixes: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn next(&mut self, text: &str) -> String {
+        let mut slug = String::new();
+        for char in text.chars().flat_map(|c| c.to_lowercase()) {
+            if char.is_alphanumeric() {
+                slug.push(char);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        let slug = slug.trim_end_matches('-');
+        let slug = if slug.is_empty() { "section" } else { slug };
+
+        // a suffixed id may already be the slug of another header, "intro 1"
+        let mut id = String::from(slug);
+        let suffix = self.suffixes.entry(String::from(slug)).or_insert(1);
+        while self.used.contains(&id) {
+            id = format!("{}-{}", slug, suffix);
+            *suffix += 1;
+        }
+        self.used.insert(id.clone());
+        id
+    }
+}
+
+pub fn toc(delta_ops: &[DeltaOp]) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    let mut anchors = Anchors::new();
+    let mut text = String::new();
+    let mut line_start: Option<(usize, usize)> = None;
+    let mut offset = 0;
+
+    for (index, op) in delta_ops.iter().enumerate() {
+        if let Value::String(str_insert) = &op.insert {
+            for char in str_insert.chars() {
+                let (op_index, line_offset) = *line_start.get_or_insert((index, offset));
+                offset += 1;
+                if char != '\n' {
+                    text.push(char);
+                    continue;
+                }
+
+                if let Some(Value::Object(attr)) = &op.attributes {
+                    if let Some(level) = header_level(attr) {
+                        entries.push(TocEntry {
+                            level,
+                            text: String::from(text.trim()),
+                            id: anchors.next(&text),
+                            op_index,
+                            offset: line_offset,
+                            children: Vec::new(),
+                        });
+                    }
+                }
+                text.clear();
+                line_start = None;
+            }
+        } else if let Value::Object(_) = &op.insert {
+            line_start.get_or_insert((index, offset));
+            offset += 1;
+        }
+    }
+
+    nest(entries)
+}
+
+// nested html list, anchors match the ids rendered with `RenderOptions::header_ids`
+pub fn render_toc(entries: &[TocEntry]) -> String {
+    if entries.is_empty() {
+        return String::from("");
+    }
+    let items = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "<li><a href=\"#{}\">{}</a>{}</li>",
+                entry.id,
+                escape_attr(&entry.text),
+                render_toc(&entry.children)
+            )
+        })
+        .collect::<String>();
+    format!("<ul>{}</ul>", items)
+}
+
+// a header becomes the child of the closest previous header with a smaller level
+fn nest(flat: Vec<TocEntry>) -> Vec<TocEntry> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<TocEntry> = Vec::new();
+    for entry in flat {
+        close_until(&mut stack, &mut roots, entry.level);
+        stack.push(entry);
+    }
+    close_until(&mut stack, &mut roots, 0);
+    roots
+}
+
+fn close_until(stack: &mut Vec<TocEntry>, roots: &mut Vec<TocEntry>, level: u64) {
+    while stack.last().is_some_and(|top| top.level >= level) {
+        let top = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(top),
+            None => roots.push(top),
+        }
+    }
+}